@@ -0,0 +1,714 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        StatusCode,
+    },
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
+    Router,
+};
+use serde::Deserialize;
+
+use crate::auth::{self, Admin, ApiResponse, Authorized, Role, UserRole, Viewer};
+use crate::counting::{CountingRule, NewCountingRule};
+use crate::devices::{self, Caller, Device, DeviceStatus, DeviceStatusChange, DeviceUpdate, DeviceWithKey, NewDevice};
+use crate::database::{
+    DailyStat, DailyStatsQuery, Database, DetectionPage, DetectionQuery, DetectionRecord, DetectionStats,
+    Throughput, ThroughputQuery,
+};
+use crate::export::{self, ExportParams, ExportWriter};
+use crate::passwords;
+use crate::sessions::{RevokeReason, Session};
+use crate::users::{NewUser, PasswordChange, User, UserUpdate};
+use crate::xlsx_export;
+use crate::AppState;
+
+// Nombre de changements de statut renvoyés par /api/devices/:id/status
+const STATUS_HISTORY_LIMIT: i64 = 100;
+
+type ApiResult<T> = Result<Json<ApiResponse<T>>, (StatusCode, Json<ApiResponse<T>>)>;
+
+// Détection envoyée par le dashboard, l'historique ou detection.py
+#[derive(Debug, Deserialize)]
+pub struct NewDetection {
+    pub g_id: String,
+    pub object_type: String,
+    pub color: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    pub g_id: Option<String>,
+}
+
+// GET /api/devices?status=offline : filtre facultatif sur le statut
+#[derive(Debug, Deserialize)]
+pub struct DevicesQuery {
+    pub status: Option<DeviceStatus>,
+}
+
+// GET /api/sessions?all=true : sessions de tous les utilisateurs (admin)
+#[derive(Debug, Deserialize)]
+pub struct SessionsQuery {
+    #[serde(default)]
+    pub all: bool,
+}
+
+// Routes montées sous /api. Chaque handler déclare son rôle minimal par son extracteur
+// (Authorized<Viewer | UserRole | Admin>) ou, pour l'envoi de détections, par Caller (appareil
+// ou rôle "user"). Seules la connexion, le rafraîchissement et la vérification de token sont ouverts
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/login", post(auth::login))
+        .route("/verify", post(auth::verify_token))
+        .route("/refresh", post(auth::refresh))
+        .route("/logout", post(auth::logout))
+        .route("/sessions", get(list_sessions))
+        .route("/sessions/:id", delete(revoke_session))
+        .route("/detection", post(create_detection))
+        .route("/detections", get(list_detections).post(create_detection))
+        .route("/history", get(list_detections))
+        .route("/download", get(download_detections))
+        .route("/download/xlsx", get(download_xlsx))
+        .route("/detections/:id", delete(delete_detection))
+        .route("/stats", get(get_stats))
+        .route("/stats/daily", get(get_daily_stats))
+        .route("/stats/throughput", get(get_throughput))
+        .route("/rules", get(list_rules).post(create_rule))
+        .route("/rules/:id", delete(delete_rule))
+        .route("/devices", get(list_devices).post(create_device))
+        .route("/devices/:id", get(get_device).put(update_device).delete(delete_device))
+        .route("/devices/heartbeat", post(device_heartbeat))
+        .route("/devices/:id/key", post(rotate_device_key))
+        .route("/devices/:id/status", get(device_status_history))
+        .route("/users", get(list_users).post(create_user))
+        .route("/users/:id", get(get_user).put(update_user).delete(delete_user))
+        .route("/password", post(change_password))
+        .route("/reset", post(reset_database))
+        .fallback(not_found)
+}
+
+// Les routes /api inconnues ne doivent pas retomber sur index.html
+async fn not_found() -> (StatusCode, Json<ApiResponse<()>>) {
+    (StatusCode::NOT_FOUND, Json(ApiResponse::error("Unknown API route")))
+}
+
+fn database_error<T>(e: sqlx::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    eprintln!("❌ Erreur base de données: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiResponse::error("Database error")),
+    )
+}
+
+// POST /api/detection (JWT rôle user ou clé d'API d'un appareil)
+async fn create_detection(
+    State(db): State<Database>,
+    caller: Caller,
+    Json(payload): Json<NewDetection>,
+) -> ApiResult<DetectionRecord> {
+    if payload.g_id.trim().is_empty() || payload.object_type.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiResponse::error("g_id and object_type are required")),
+        ));
+    }
+
+    let detection = db
+        .insert_manual_detection(
+            &payload.g_id,
+            &payload.object_type,
+            &payload.color,
+            caller.device().map(|device| device.id),
+        )
+        .await
+        .map_err(database_error)?;
+
+    println!(
+        "📥 Détection enregistrée: {} ({}) par {}",
+        payload.object_type,
+        payload.color,
+        caller.label()
+    );
+    Ok(Json(ApiResponse::success(detection)))
+}
+
+// GET /api/detections
+async fn list_detections(
+    State(db): State<Database>,
+    _: Authorized<Viewer>,
+    Query(query): Query<DetectionQuery>,
+) -> ApiResult<DetectionPage> {
+    let cursor = query
+        .page_cursor()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let page = db
+        .search_detections(&query, cursor.as_ref())
+        .await
+        .map_err(database_error)?;
+
+    Ok(Json(ApiResponse::success(page)))
+}
+
+// GET /api/download?format=csv|json|ndjson|txt
+async fn download_detections(
+    State(db): State<Database>,
+    Authorized { user, .. }: Authorized<Viewer>,
+    Query(query): Query<DetectionQuery>,
+    Query(params): Query<ExportParams>,
+) -> Result<Response, (StatusCode, Json<ApiResponse<()>>)> {
+    let format = params.format;
+    let filename = format!(
+        "detections_{}.{}",
+        chrono::Local::now().format("%Y%m%d_%H%M%S"),
+        format.extension()
+    );
+    let writer = ExportWriter::new(format, export::report_title(&query));
+    let body = export::stream_body(db.stream_detections(query), writer);
+
+    println!("📤 Export {} demandé par: {}", format.extension(), user.username);
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+// GET /api/download/xlsx
+async fn download_xlsx(
+    State(db): State<Database>,
+    Authorized { user, .. }: Authorized<Viewer>,
+    Query(query): Query<DetectionQuery>,
+) -> Result<Response, (StatusCode, Json<ApiResponse<()>>)> {
+    let cadence = db.daily_cadence(&query).await.map_err(database_error)?;
+    let records = db.stream_detections(query);
+    let workbook = tokio::task::spawn_blocking(move || xlsx_export::build_workbook(records, &cadence))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|result| result)
+        .map_err(|e| {
+            eprintln!("❌ Export XLSX impossible: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error("Export failed")),
+            )
+        })?;
+
+    let filename = format!("detections_{}.xlsx", chrono::Local::now().format("%Y%m%d_%H%M%S"));
+    println!("📤 Export xlsx demandé par: {}", user.username);
+    Ok((
+        [
+            (CONTENT_TYPE, xlsx_export::CONTENT_TYPE.to_string()),
+            (CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        workbook,
+    )
+        .into_response())
+}
+
+// DELETE /api/detections/:id
+async fn delete_detection(
+    State(db): State<Database>,
+    _: Authorized<Admin>,
+    Path(id): Path<i64>,
+) -> ApiResult<i64> {
+    if !db.delete_detection(id).await.map_err(database_error)? {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Detection not found")),
+        ));
+    }
+
+    Ok(Json(ApiResponse::success(id)))
+}
+
+// GET /api/stats
+async fn get_stats(
+    State(db): State<Database>,
+    _: Authorized<Viewer>,
+    Query(query): Query<StatsQuery>,
+) -> ApiResult<DetectionStats> {
+    let stats = db
+        .get_stats(query.g_id.as_deref())
+        .await
+        .map_err(database_error)?;
+
+    Ok(Json(ApiResponse::success(stats)))
+}
+
+// GET /api/stats/daily?from=&to=&group_by=type,color
+async fn get_daily_stats(
+    State(db): State<Database>,
+    _: Authorized<Viewer>,
+    Query(query): Query<DailyStatsQuery>,
+) -> ApiResult<Vec<DailyStat>> {
+    let group_columns = query
+        .group_columns()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let stats = db
+        .get_daily_stats(&query, &group_columns)
+        .await
+        .map_err(database_error)?;
+
+    Ok(Json(ApiResponse::success(stats)))
+}
+
+// GET /api/stats/throughput?interval=minute|hour|day&from=&to=
+async fn get_throughput(
+    State(db): State<Database>,
+    _: Authorized<Viewer>,
+    Query(query): Query<ThroughputQuery>,
+) -> ApiResult<Throughput> {
+    let (from, to) = query
+        .range()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let throughput = db
+        .get_throughput(&query, from, to)
+        .await
+        .map_err(database_error)?;
+
+    Ok(Json(ApiResponse::success(throughput)))
+}
+
+// GET /api/rules?g_id=
+async fn list_rules(
+    State(db): State<Database>,
+    _: Authorized<Viewer>,
+    Query(query): Query<StatsQuery>,
+) -> ApiResult<Vec<CountingRule>> {
+    let rules = db
+        .list_counting_rules(query.g_id.as_deref())
+        .await
+        .map_err(database_error)?;
+
+    Ok(Json(ApiResponse::success(rules)))
+}
+
+// POST /api/rules
+async fn create_rule(
+    State(db): State<Database>,
+    Authorized { user, .. }: Authorized<UserRole>,
+    Json(payload): Json<NewCountingRule>,
+) -> ApiResult<CountingRule> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let rule = db.insert_counting_rule(&payload).await.map_err(|e| {
+        if e.as_database_error().is_some_and(|e| e.is_unique_violation()) {
+            (
+                StatusCode::CONFLICT,
+                Json(ApiResponse::error("A rule with this name already exists for this g_id")),
+            )
+        } else {
+            database_error(e)
+        }
+    })?;
+
+    println!("📐 Règle {} créée pour {} par: {}", rule.name, rule.g_id, user.username);
+    Ok(Json(ApiResponse::success(rule)))
+}
+
+// DELETE /api/rules/:id
+async fn delete_rule(
+    State(db): State<Database>,
+    _: Authorized<Admin>,
+    Path(id): Path<i64>,
+) -> ApiResult<i64> {
+    if !db.delete_counting_rule(id).await.map_err(database_error)? {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error("Rule not found")),
+        ));
+    }
+
+    Ok(Json(ApiResponse::success(id)))
+}
+
+fn device_not_found<T>() -> (StatusCode, Json<ApiResponse<T>>) {
+    (StatusCode::NOT_FOUND, Json(ApiResponse::error("Device not found")))
+}
+
+// Nom d'appareil déjà pris : 409 plutôt qu'une erreur base de données
+fn device_write_error<T>(e: sqlx::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    if e.as_database_error().is_some_and(|e| e.is_unique_violation()) {
+        (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error("A device with this name already exists")),
+        )
+    } else {
+        database_error(e)
+    }
+}
+
+// GET /api/devices?status=offline (viewer)
+async fn list_devices(
+    State(db): State<Database>,
+    _: Authorized<Viewer>,
+    Query(query): Query<DevicesQuery>,
+) -> ApiResult<Vec<Device>> {
+    let devices = db.list_devices(query.status).await.map_err(database_error)?;
+    Ok(Json(ApiResponse::success(devices)))
+}
+
+// GET /api/devices/:id
+async fn get_device(
+    State(db): State<Database>,
+    _: Authorized<Viewer>,
+    Path(id): Path<i64>,
+) -> ApiResult<Device> {
+    let device = db
+        .get_device(id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(device_not_found)?;
+    Ok(Json(ApiResponse::success(device)))
+}
+
+// POST /api/devices : la clé d'API n'est renvoyée qu'une seule fois
+async fn create_device(
+    State(db): State<Database>,
+    Authorized { user, .. }: Authorized<Admin>,
+    Json(payload): Json<NewDevice>,
+) -> ApiResult<DeviceWithKey> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let api_key = devices::generate_api_key();
+    let device = db
+        .insert_device(&payload, &api_key)
+        .await
+        .map_err(device_write_error)?;
+
+    println!("📷 Appareil {} créé par: {}", device.name, user.username);
+    Ok(Json(ApiResponse::success(DeviceWithKey { device, api_key })))
+}
+
+// PUT /api/devices/:id
+async fn update_device(
+    State(db): State<Database>,
+    _: Authorized<Admin>,
+    Path(id): Path<i64>,
+    Json(payload): Json<DeviceUpdate>,
+) -> ApiResult<Device> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let device = db
+        .update_device(id, &payload)
+        .await
+        .map_err(device_write_error)?
+        .ok_or_else(device_not_found)?;
+    Ok(Json(ApiResponse::success(device)))
+}
+
+// POST /api/devices/:id/key : nouvelle clé, l'ancienne est révoquée
+async fn rotate_device_key(
+    State(db): State<Database>,
+    Authorized { user, .. }: Authorized<Admin>,
+    Path(id): Path<i64>,
+) -> ApiResult<DeviceWithKey> {
+    let api_key = devices::generate_api_key();
+    let device = db
+        .rotate_device_key(id, &api_key)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(device_not_found)?;
+
+    println!("🔑 Clé de l'appareil {} renouvelée par: {}", device.name, user.username);
+    Ok(Json(ApiResponse::success(DeviceWithKey { device, api_key })))
+}
+
+// POST /api/devices/heartbeat (X-API-Key) : le battement est enregistré par l'extracteur Caller
+async fn device_heartbeat(caller: Caller) -> ApiResult<Device> {
+    match caller {
+        Caller::Device(device) => Ok(Json(ApiResponse::success(device))),
+        _ => Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ApiResponse::error("Missing device API key")),
+        )),
+    }
+}
+
+// GET /api/devices/:id/status : historique des passages en ligne / hors ligne
+async fn device_status_history(
+    State(db): State<Database>,
+    _: Authorized<Viewer>,
+    Path(id): Path<i64>,
+) -> ApiResult<Vec<DeviceStatusChange>> {
+    db.get_device(id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(device_not_found)?;
+    let history = db
+        .device_status_history(id, STATUS_HISTORY_LIMIT)
+        .await
+        .map_err(database_error)?;
+
+    Ok(Json(ApiResponse::success(history)))
+}
+
+// DELETE /api/devices/:id
+async fn delete_device(
+    State(db): State<Database>,
+    _: Authorized<Admin>,
+    Path(id): Path<i64>,
+) -> ApiResult<i64> {
+    if !db.delete_device(id).await.map_err(database_error)? {
+        return Err(device_not_found());
+    }
+
+    Ok(Json(ApiResponse::success(id)))
+}
+
+fn password_error<T>(e: impl std::fmt::Display) -> (StatusCode, Json<ApiResponse<T>>) {
+    eprintln!("❌ Hachage du mot de passe impossible: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiResponse::error("Password hashing failed")),
+    )
+}
+
+fn user_not_found<T>() -> (StatusCode, Json<ApiResponse<T>>) {
+    (StatusCode::NOT_FOUND, Json(ApiResponse::error("User not found")))
+}
+
+// Nom d'utilisateur déjà pris : 409 plutôt qu'une erreur base de données
+fn user_write_error<T>(e: sqlx::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    if e.as_database_error().is_some_and(|e| e.is_unique_violation()) {
+        (
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error("A user with this name already exists")),
+        )
+    } else {
+        database_error(e)
+    }
+}
+
+// Refuser de supprimer, désactiver ou rétrograder le dernier administrateur actif
+async fn ensure_other_admin<T>(db: &Database) -> Result<(), (StatusCode, Json<ApiResponse<T>>)> {
+    if db.count_active_admins().await.map_err(database_error)? <= 1 {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(ApiResponse::error("Cannot remove the last active admin")),
+        ));
+    }
+    Ok(())
+}
+
+// GET /api/users (admin)
+async fn list_users(State(db): State<Database>, _: Authorized<Admin>) -> ApiResult<Vec<User>> {
+    let users = db.list_users().await.map_err(database_error)?;
+    Ok(Json(ApiResponse::success(users)))
+}
+
+// GET /api/users/:id (admin)
+async fn get_user(
+    State(db): State<Database>,
+    _: Authorized<Admin>,
+    Path(id): Path<i64>,
+) -> ApiResult<User> {
+    let user = db
+        .get_user(id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(user_not_found)?;
+    Ok(Json(ApiResponse::success(user)))
+}
+
+// POST /api/users (admin)
+async fn create_user(
+    State(db): State<Database>,
+    Authorized { user: admin, .. }: Authorized<Admin>,
+    Json(payload): Json<NewUser>,
+) -> ApiResult<User> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let password_hash = passwords::hash_password(&payload.password)
+        .await
+        .map_err(password_error)?;
+    let user = db
+        .insert_user(&payload, &password_hash)
+        .await
+        .map_err(user_write_error)?;
+
+    println!("👤 Utilisateur {} ({}) créé par: {}", user.username, user.role, admin.username);
+    Ok(Json(ApiResponse::success(user)))
+}
+
+// PUT /api/users/:id (admin) : rôle, activation ou nouveau mot de passe
+async fn update_user(
+    State(db): State<Database>,
+    Authorized { user: admin, .. }: Authorized<Admin>,
+    Path(id): Path<i64>,
+    Json(payload): Json<UserUpdate>,
+) -> ApiResult<User> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let current = db
+        .get_user(id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(user_not_found)?;
+    if payload.revokes_admin(&current) {
+        ensure_other_admin(&db).await?;
+    }
+
+    let password_hash = match &payload.password {
+        Some(password) => Some(passwords::hash_password(password).await.map_err(password_error)?),
+        None => None,
+    };
+    let user = db
+        .update_user(id, &payload, password_hash.as_deref())
+        .await
+        .map_err(database_error)?
+        .ok_or_else(user_not_found)?;
+
+    // Nouveau rôle, désactivation ou mot de passe imposé : les sessions ouvertes ne valent plus
+    if user.role != current.role || user.enabled != current.enabled || password_hash.is_some() {
+        let revoked = db
+            .revoke_user_sessions(user.id, None, RevokeReason::AccountChanged)
+            .await
+            .map_err(database_error)?;
+        if revoked > 0 {
+            println!("🔒 {} session(s) de {} révoquée(s)", revoked, user.username);
+        }
+    }
+
+    println!("👤 Utilisateur {} modifié par: {}", user.username, admin.username);
+    Ok(Json(ApiResponse::success(user)))
+}
+
+// DELETE /api/users/:id (admin)
+async fn delete_user(
+    State(db): State<Database>,
+    Authorized { user: admin, .. }: Authorized<Admin>,
+    Path(id): Path<i64>,
+) -> ApiResult<i64> {
+    let user = db
+        .get_user(id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(user_not_found)?;
+    if user.enabled && user.role == Role::Admin.as_str() {
+        ensure_other_admin(&db).await?;
+    }
+
+    if !db.delete_user(id).await.map_err(database_error)? {
+        return Err(user_not_found());
+    }
+
+    println!("🗑️ Utilisateur {} supprimé par: {}", user.username, admin.username);
+    Ok(Json(ApiResponse::success(id)))
+}
+
+// POST /api/password : l'utilisateur connecté change son propre mot de passe
+async fn change_password(
+    State(db): State<Database>,
+    Authorized { user: caller, session_id, .. }: Authorized<Viewer>,
+    Json(payload): Json<PasswordChange>,
+) -> ApiResult<()> {
+    payload
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ApiResponse::error(&e))))?;
+
+    let user = auth::verify_user_credentials(&db, &caller.username, &payload.current_password)
+        .await
+        .map_err(password_error)?
+        .ok_or((
+            StatusCode::UNAUTHORIZED,
+            Json(ApiResponse::error("Invalid current password")),
+        ))?;
+
+    let password_hash = passwords::hash_password(&payload.new_password)
+        .await
+        .map_err(password_error)?;
+    db.set_user_password(user.id, &password_hash)
+        .await
+        .map_err(database_error)?;
+    // Déconnecter les autres appareils, la session courante reste ouverte
+    db.revoke_user_sessions(user.id, Some(&session_id), RevokeReason::PasswordChanged)
+        .await
+        .map_err(database_error)?;
+
+    println!("🔑 Mot de passe changé pour l'utilisateur: {}", user.username);
+    Ok(Json(ApiResponse::success(())))
+}
+
+fn session_not_found<T>() -> (StatusCode, Json<ApiResponse<T>>) {
+    (StatusCode::NOT_FOUND, Json(ApiResponse::error("Session not found")))
+}
+
+// Session de la requête, pour retrouver l'identifiant du compte connecté
+async fn current_session<T>(db: &Database, session_id: &str) -> Result<Session, (StatusCode, Json<ApiResponse<T>>)> {
+    db.get_session(session_id)
+        .await
+        .map_err(database_error)?
+        .ok_or_else(session_not_found)
+}
+
+// GET /api/sessions : sessions actives du compte connecté (toutes avec ?all=true pour un admin)
+async fn list_sessions(
+    State(db): State<Database>,
+    Authorized { user, session_id, .. }: Authorized<Viewer>,
+    Query(query): Query<SessionsQuery>,
+) -> ApiResult<Vec<Session>> {
+    let owner = if query.all {
+        auth::check_role(&user, Role::Admin, "/api/sessions?all=true")
+            .map_err(|(status, Json(body))| (status, Json(ApiResponse::error(&body.message))))?;
+        None
+    } else {
+        Some(current_session(&db, &session_id).await?.user_id)
+    };
+
+    let mut sessions = db.list_sessions(owner).await.map_err(database_error)?;
+    for session in &mut sessions {
+        session.current = session.id == session_id;
+    }
+    Ok(Json(ApiResponse::success(sessions)))
+}
+
+// DELETE /api/sessions/:id : révoquer une de ses sessions (n'importe laquelle pour un admin)
+async fn revoke_session(
+    State(db): State<Database>,
+    Authorized { user, session_id, .. }: Authorized<Viewer>,
+    Path(id): Path<String>,
+) -> ApiResult<String> {
+    let owner = if user.role() == Some(Role::Admin) {
+        None
+    } else {
+        Some(current_session(&db, &session_id).await?.user_id)
+    };
+
+    if !db
+        .revoke_session(&id, owner, RevokeReason::Revoked)
+        .await
+        .map_err(database_error)?
+    {
+        return Err(session_not_found());
+    }
+
+    println!("🔒 Session {} révoquée par: {}", id, user.username);
+    Ok(Json(ApiResponse::success(id)))
+}
+
+// POST /api/reset
+async fn reset_database(State(db): State<Database>, Authorized { user, .. }: Authorized<Admin>) -> ApiResult<()> {
+    db.reset().await.map_err(database_error)?;
+
+    println!("🗑️ Base réinitialisée par: {}", user.username);
+    Ok(Json(ApiResponse::success(())))
+}
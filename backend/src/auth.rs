@@ -1,16 +1,23 @@
 use axum::{
-    http::StatusCode,
+    async_trait,
+    extract::{FromRef, FromRequestParts, OriginalUri, State},
+    http::{
+        header::{AUTHORIZATION, USER_AGENT},
+        request::Parts,
+        HeaderMap, StatusCode,
+    },
     response::Json,
 };
 use serde::{Deserialize, Serialize};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation, Algorithm};
-use chrono::{Utc, Duration};
-use sha2::{Sha256, Digest};
+use chrono::Utc;
+use std::fmt;
+use std::marker::PhantomData;
 
-// Configuration par défaut (à changer en production)
-const DEFAULT_USERNAME: &str = "admin";
-const DEFAULT_PASSWORD: &str = "password123";
-const JWT_SECRET: &str = "your-super-secret-jwt-key-change-in-production-123456789";
+use crate::database::Database;
+use crate::jwt_keys;
+use crate::passwords::{self, PasswordCheck, PasswordError};
+use crate::sessions::{self, RefreshOutcome, RevokeReason};
+use crate::users::User;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
@@ -18,19 +25,63 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+// Réponse de /api/login et /api/refresh
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginResponse {
+    // Jeton d'accès, à envoyer dans "Authorization: Bearer ..."
     pub token: String,
     pub expires_at: i64,
+    // Jeton de rafraîchissement à usage unique, échangé via /api/refresh
+    pub refresh_token: String,
+    pub refresh_expires_at: i64,
     pub user: UserInfo,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserInfo {
     pub username: String,
     pub role: String,
 }
 
+impl UserInfo {
+    // Rôle inconnu (token forgé avant un changement de rôles) : aucun droit
+    pub fn role(&self) -> Option<Role> {
+        Role::parse(&self.role)
+    }
+}
+
+// Rôles, du moins au plus privilégié : chaque rôle hérite des droits des précédents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    // Consultation de l'historique et des statistiques
+    Viewer,
+    // Envoi de détections et règles de comptage
+    User,
+    // Suppressions, remise à zéro, appareils et comptes
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Admin, Role::User, Role::Viewer];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::User => "user",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Role::ALL.into_iter().find(|role| role.as_str() == value)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TokenRequest {
     pub token: String,
@@ -42,6 +93,7 @@ pub struct Claims {
     pub exp: i64,     // Expiration time
     pub iat: i64,     // Issued at
     pub role: String, // User role
+    pub sid: String,  // Session (révocable côté serveur)
 }
 
 #[derive(Serialize)]
@@ -69,224 +121,346 @@ impl<T> ApiResponse<T> {
     }
 }
 
-// Fonction de hashage des mots de passe
-fn hash_password(password: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(password.as_bytes());
-    let result = hasher.finalize();
-    format!("{:x}", result)
+#[derive(Debug)]
+pub enum CredentialsError {
+    Database(sqlx::Error),
+    Password(PasswordError),
+}
+
+impl fmt::Display for CredentialsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CredentialsError::Database(e) => write!(f, "Database error: {}", e),
+            CredentialsError::Password(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CredentialsError {}
+
+impl From<sqlx::Error> for CredentialsError {
+    fn from(e: sqlx::Error) -> Self {
+        CredentialsError::Database(e)
+    }
+}
+
+impl From<PasswordError> for CredentialsError {
+    fn from(e: PasswordError) -> Self {
+        CredentialsError::Password(e)
+    }
 }
 
-// Vérifier les identifiants utilisateur
-fn verify_user_credentials(username: &str, password: &str) -> bool {
-    // En production, ceci devrait vérifier contre une base de données
-    if username == DEFAULT_USERNAME {
-        let hashed_input = hash_password(password);
-        let hashed_default = hash_password(DEFAULT_PASSWORD);
-        return hashed_input == hashed_default;
+// Vérifier les identifiants contre la table users (None si inconnus, erronés ou compte désactivé).
+// Un hash obsolète est recalculé avec les paramètres courants après une vérification réussie.
+pub async fn verify_user_credentials(
+    db: &Database,
+    username: &str,
+    password: &str,
+) -> Result<Option<User>, CredentialsError> {
+    let Some((user, password_hash)) = db.find_user_credentials(username).await? else {
+        // Même coût qu'un compte existant, pour ne pas révéler les noms valides
+        passwords::hash_password(password).await?;
+        return Ok(None);
+    };
+
+    let check = passwords::verify_password(password, &password_hash).await?;
+    if !check.is_valid() {
+        return Ok(None);
+    }
+
+    if !user.enabled {
+        println!("⛔ Compte désactivé: {}", user.username);
+        return Ok(None);
     }
-    
-    // Ajouter d'autres utilisateurs ici si nécessaire
-    false
+
+    if check == PasswordCheck::NeedsRehash {
+        let rehashed = passwords::hash_password(password).await?;
+        db.set_user_password(user.id, &rehashed).await?;
+        println!("🔁 Hash du mot de passe mis à jour pour: {}", user.username);
+    }
+
+    Ok(Some(user))
 }
 
-// Générer un JWT token
-fn generate_jwt_token(username: &str, role: &str) -> Result<String, jsonwebtoken::errors::Error> {
+// Générer un jeton d'accès lié à une session
+fn generate_jwt_token(username: &str, role: &str, session_id: &str) -> Result<(String, i64), jsonwebtoken::errors::Error> {
     let now = Utc::now();
-    let expires_at = now + Duration::hours(24); // Token valide 24h
+    let expires_at = now + sessions::config().access_ttl;
 
     let claims = Claims {
         sub: username.to_string(),
         exp: expires_at.timestamp(),
         iat: now.timestamp(),
         role: role.to_string(),
+        sid: session_id.to_string(),
     };
 
-    let header = Header::new(Algorithm::HS256);
-    let encoding_key = EncodingKey::from_secret(JWT_SECRET.as_bytes());
-
-    encode(&header, &claims, &encoding_key)
+    Ok((jwt_keys::keys().sign(&claims)?, expires_at.timestamp()))
 }
 
-// Vérifier un JWT token
-pub fn verify_jwt_token(token: &str) -> bool {
-    let decoding_key = DecodingKey::from_secret(JWT_SECRET.as_bytes());
-    let validation = Validation::new(Algorithm::HS256);
-
-    match decode::<Claims>(token, &decoding_key, &validation) {
-        Ok(token_data) => {
+// Vérifier la signature et l'expiration d'un jeton d'accès
+fn decode_token(token: &str) -> Result<Claims, &'static str> {
+    match jwt_keys::keys().verify::<Claims>(token) {
+        Ok(claims) => {
             // Vérifier si le token n'est pas expiré
-            let now = Utc::now().timestamp();
-            if token_data.claims.exp < now {
-                println!("🔒 Token expiré pour l'utilisateur: {}", token_data.claims.sub);
-                return false;
+            if claims.exp < Utc::now().timestamp() {
+                println!("🔒 Token expiré pour l'utilisateur: {}", claims.sub);
+                return Err("Invalid or expired token");
             }
-            
-            println!("✅ Token valide pour l'utilisateur: {}", token_data.claims.sub);
-            true
+            Ok(claims)
         }
         Err(e) => {
             println!("❌ Token invalide: {}", e);
-            false
+            Err("Invalid or expired token")
         }
     }
 }
 
-// Extraire les informations du token
-pub fn extract_user_from_token(token: &str) -> Option<UserInfo> {
-    let decoding_key = DecodingKey::from_secret(JWT_SECRET.as_bytes());
-    let validation = Validation::new(Algorithm::HS256);
-
-    match decode::<Claims>(token, &decoding_key, &validation) {
-        Ok(token_data) => {
-            let now = Utc::now().timestamp();
-            if token_data.claims.exp >= now {
-                Some(UserInfo {
-                    username: token_data.claims.sub,
-                    role: token_data.claims.role,
-                })
-            } else {
-                None
-            }
-        }
-        Err(_) => None,
+// Jeton valide et session non révoquée : utilisateur et identifiant de session
+pub async fn authenticate(db: &Database, token: &str) -> Result<(UserInfo, String), Rejection> {
+    let claims = decode_token(token).map_err(|e| reject(StatusCode::UNAUTHORIZED, e))?;
+
+    let active = db.session_is_active(&claims.sid).await.map_err(|e| {
+        eprintln!("❌ Erreur base de données: {}", e);
+        reject(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+    })?;
+    if !active {
+        println!("🔒 Session révoquée pour l'utilisateur: {}", claims.sub);
+        return Err(reject(StatusCode::UNAUTHORIZED, "Session revoked"));
     }
+
+    let user = UserInfo {
+        username: claims.sub,
+        role: claims.role,
+    };
+    Ok((user, claims.sid))
+}
+
+type AuthResult<T> = Result<Json<ApiResponse<T>>, (StatusCode, Json<ApiResponse<T>>)>;
+
+fn token_error<T>(e: jsonwebtoken::errors::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    eprintln!("❌ Erreur lors de la génération du token: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiResponse::error("Failed to generate authentication token")),
+    )
 }
 
-// Route de connexion
+fn session_error<T>(e: sqlx::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    eprintln!("❌ Erreur base de données: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(ApiResponse::error("Database error")),
+    )
+}
+
+// Jeton d'accès et jeton de rafraîchissement d'une session
+fn issue_tokens(user: &User, session_id: &str, refresh_token: String) -> Result<LoginResponse, jsonwebtoken::errors::Error> {
+    let (token, expires_at) = generate_jwt_token(&user.username, &user.role, session_id)?;
+    Ok(LoginResponse {
+        token,
+        expires_at,
+        refresh_token,
+        refresh_expires_at: (Utc::now() + sessions::config().refresh_ttl).timestamp(),
+        user: UserInfo {
+            username: user.username.clone(),
+            role: user.role.clone(),
+        },
+    })
+}
+
+// Route de connexion : ouvre une session
 pub async fn login(
+    State(db): State<Database>,
+    headers: HeaderMap,
     Json(login_request): Json<LoginRequest>,
-) -> Result<Json<ApiResponse<LoginResponse>>, (StatusCode, Json<ApiResponse<LoginResponse>>)> {
+) -> AuthResult<LoginResponse> {
     println!("🔐 Tentative de connexion pour: {}", login_request.username);
 
     // Vérifier les identifiants
-    if !verify_user_credentials(&login_request.username, &login_request.password) {
+    let user = verify_user_credentials(&db, &login_request.username, &login_request.password)
+        .await
+        .map_err(|e| {
+            eprintln!("❌ Vérification des identifiants impossible: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ApiResponse::error("Failed to verify credentials")),
+            )
+        })?;
+    let Some(user) = user else {
         println!("❌ Identifiants invalides pour: {}", login_request.username);
         return Err((
             StatusCode::UNAUTHORIZED,
             Json(ApiResponse::error("Invalid username or password")),
         ));
-    }
-
-    // Déterminer le rôle (en production, ceci viendrait de la DB)
-    let role = if login_request.username == DEFAULT_USERNAME {
-        "admin"
-    } else {
-        "user"
     };
 
-    // Générer le token JWT
-    match generate_jwt_token(&login_request.username, role) {
-        Ok(token) => {
-            let expires_at = (Utc::now() + Duration::hours(24)).timestamp();
-            
-            let response = LoginResponse {
-                token: token.clone(),
-                expires_at,
-                user: UserInfo {
-                    username: login_request.username.clone(),
-                    role: role.to_string(),
-                },
-            };
-
-            println!("✅ Connexion réussie pour: {} (rôle: {})", login_request.username, role);
+    let session_id = sessions::generate_session_id();
+    let refresh_token = sessions::generate_refresh_token();
+    let user_agent = headers.get(USER_AGENT).and_then(|value| value.to_str().ok());
+    db.create_session(
+        &session_id,
+        user.id,
+        user_agent,
+        &sessions::hash_refresh_token(&refresh_token),
+        sessions::config().refresh_ttl,
+    )
+    .await
+    .map_err(session_error)?;
+
+    let response = issue_tokens(&user, &session_id, refresh_token).map_err(token_error)?;
+    println!("✅ Connexion réussie pour: {} (rôle: {})", user.username, user.role);
+    Ok(Json(ApiResponse::success(response)))
+}
+
+// Route de rafraîchissement : le jeton présenté est consommé et remplacé
+pub async fn refresh(
+    State(db): State<Database>,
+    Json(request): Json<RefreshRequest>,
+) -> AuthResult<LoginResponse> {
+    let refresh_token = sessions::generate_refresh_token();
+    let outcome = db
+        .rotate_refresh_token(
+            &sessions::hash_refresh_token(&request.refresh_token),
+            &sessions::hash_refresh_token(&refresh_token),
+            sessions::config().refresh_ttl,
+        )
+        .await
+        .map_err(session_error)?;
+
+    match outcome {
+        RefreshOutcome::Rotated { session_id, user } => {
+            let response = issue_tokens(&user, &session_id, refresh_token).map_err(token_error)?;
             Ok(Json(ApiResponse::success(response)))
         }
-        Err(e) => {
-            eprintln!("❌ Erreur lors de la génération du token: {}", e);
+        RefreshOutcome::Reused { session_id } => {
+            println!("🚨 Jeton de rafraîchissement réutilisé, session {} révoquée", session_id);
             Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ApiResponse::error("Failed to generate authentication token")),
+                StatusCode::UNAUTHORIZED,
+                Json(ApiResponse::error("Refresh token reuse detected, session revoked")),
             ))
         }
+        RefreshOutcome::Invalid => Err((
+            StatusCode::UNAUTHORIZED,
+            Json(ApiResponse::error("Invalid or expired refresh token")),
+        )),
     }
 }
 
+// Route de déconnexion : révoque la session du jeton d'accès
+pub async fn logout(
+    State(db): State<Database>,
+    Authorized { user, session_id, .. }: Authorized<Viewer>,
+) -> AuthResult<()> {
+    db.revoke_session(&session_id, None, RevokeReason::Logout)
+        .await
+        .map_err(session_error)?;
+
+    println!("👋 Déconnexion de: {}", user.username);
+    Ok(Json(ApiResponse::success(())))
+}
+
 // Route de vérification de token
 pub async fn verify_token(
+    State(db): State<Database>,
     Json(token_request): Json<TokenRequest>,
-) -> Result<Json<ApiResponse<UserInfo>>, (StatusCode, Json<ApiResponse<UserInfo>>)> {
+) -> Result<Json<ApiResponse<UserInfo>>, Rejection> {
     println!("🔍 Vérification du token...");
 
-    if verify_jwt_token(&token_request.token) {
-        if let Some(user_info) = extract_user_from_token(&token_request.token) {
-            println!("✅ Token valide pour: {}", user_info.username);
-            Ok(Json(ApiResponse::success(user_info)))
-        } else {
-            println!("❌ Impossible d'extraire les infos utilisateur du token");
-            Err((
-                StatusCode::UNAUTHORIZED,
-                Json(ApiResponse::error("Invalid token format")),
-            ))
-        }
-    } else {
-        println!("❌ Token invalide ou expiré");
-        Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ApiResponse::error("Invalid or expired token")),
-        ))
-    }
+    let (user_info, _) = authenticate(&db, &token_request.token).await?;
+    println!("✅ Token valide pour: {}", user_info.username);
+    Ok(Json(ApiResponse::success(user_info)))
 }
 
-// Middleware pour vérifier l'authentification (à utiliser dans les routes protégées)
-pub fn require_auth(token: &str) -> Result<UserInfo, &'static str> {
-    if verify_jwt_token(token) {
-        if let Some(user_info) = extract_user_from_token(token) {
-            Ok(user_info)
-        } else {
-            Err("Invalid token format")
-        }
-    } else {
-        Err("Invalid or expired token")
-    }
+// Token de l'en-tête "Authorization: Bearer ..."
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
 }
 
-// Fonction pour créer un nouvel utilisateur (pour usage futur)
-pub fn create_user(username: &str, password: &str, role: &str) -> Result<UserInfo, &'static str> {
-    // En production, ceci sauvegarderait dans la base de données
-    if username.len() < 3 {
-        return Err("Username must be at least 3 characters long");
-    }
-    
-    if password.len() < 6 {
-        return Err("Password must be at least 6 characters long");
-    }
-    
-    let valid_roles = ["admin", "user", "viewer"];
-    if !valid_roles.contains(&role) {
-        return Err("Invalid role specified");
-    }
-    
-    // Ici on sauvegarderait l'utilisateur avec le mot de passe hashé
-    let _hashed_password = hash_password(password);
-    
-    println!("👤 Nouvel utilisateur créé: {} (rôle: {})", username, role);
-    
-    Ok(UserInfo {
-        username: username.to_string(),
-        role: role.to_string(),
-    })
+pub type Rejection = (StatusCode, Json<ApiResponse<()>>);
+
+pub fn reject(status: StatusCode, message: &str) -> Rejection {
+    (status, Json(ApiResponse::error(message)))
 }
 
-// Fonction pour changer le mot de passe (pour usage futur)
-pub fn change_password(username: &str, old_password: &str, new_password: &str) -> Result<bool, &'static str> {
-    // Vérifier l'ancien mot de passe
-    if !verify_user_credentials(username, old_password) {
-        return Err("Invalid current password");
+// Chemin complet de la requête (préfixe /api compris), pour les logs
+pub fn request_path(parts: &Parts) -> &str {
+    parts
+        .extensions
+        .get::<OriginalUri>()
+        .map_or(parts.uri.path(), |uri| uri.path())
+}
+
+// Rôle minimal exigé par une route, déclaré par le type de l'extracteur
+pub trait RequiredRole {
+    const ROLE: Role;
+}
+
+// Un marqueur par variante de Role (UserRole pour Role::User, User désignant déjà le compte)
+pub struct Viewer;
+pub struct UserRole;
+pub struct Admin;
+
+impl RequiredRole for Viewer {
+    const ROLE: Role = Role::Viewer;
+}
+
+impl RequiredRole for UserRole {
+    const ROLE: Role = Role::User;
+}
+
+impl RequiredRole for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+// Utilisateur authentifié par JWT, avec une session active, et disposant au moins du rôle R :
+// 401 sans token valide ou si la session est révoquée, 403 si le rôle est insuffisant
+pub struct Authorized<R> {
+    pub user: UserInfo,
+    pub session_id: String,
+    role: PhantomData<R>,
+}
+
+// Vérifier le rôle d'un utilisateur déjà authentifié
+pub fn check_role(user: &UserInfo, required: Role, path: &str) -> Result<(), Rejection> {
+    if user.role().is_some_and(|role| role >= required) {
+        return Ok(());
     }
-    
-    if new_password.len() < 6 {
-        return Err("New password must be at least 6 characters long");
+    println!("⛔ Accès refusé à {} pour: {} (rôle {}, requis: {})", path, user.username, user.role, required.as_str());
+    Err(reject(
+        StatusCode::FORBIDDEN,
+        &format!("Insufficient role: {} required", required.as_str()),
+    ))
+}
+
+#[async_trait]
+impl<S, R> FromRequestParts<S> for Authorized<R>
+where
+    Database: FromRef<S>,
+    S: Send + Sync,
+    R: RequiredRole,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers)
+            .ok_or_else(|| reject(StatusCode::UNAUTHORIZED, "Missing bearer token"))?;
+        let (user, session_id) = authenticate(&Database::from_ref(state), token).await?;
+        check_role(&user, R::ROLE, request_path(parts))?;
+
+        Ok(Self {
+            user,
+            session_id,
+            role: PhantomData,
+        })
     }
-    
-    // En production, ceci mettrait à jour la base de données
-    let _new_hashed_password = hash_password(new_password);
-    
-    println!("🔑 Mot de passe changé pour l'utilisateur: {}", username);
-    Ok(true)
 }
 
 // Configuration des en-têtes de sécurité
+#[allow(dead_code)]
 pub fn security_headers() -> Vec<(&'static str, &'static str)> {
     vec![
         ("X-Frame-Options", "DENY"),
@@ -300,34 +474,107 @@ pub fn security_headers() -> Vec<(&'static str, &'static str)> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::migrations;
+    use crate::users::UserUpdate;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn test_db() -> Database {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        migrations::run(&pool).await.unwrap();
+        Database::new(pool)
+    }
 
-    #[test]
-    fn test_password_hashing() {
-        let password = "test123";
-        let hash1 = hash_password(password);
-        let hash2 = hash_password(password);
-        assert_eq!(hash1, hash2);
+    // Jeton d'accès adossé à une session du compte n°1
+    async fn session_token(db: &Database, username: &str, role: &str) -> (String, String) {
+        let session_id = sessions::generate_session_id();
+        db.create_session(&session_id, 1, None, &sessions::generate_refresh_token(), chrono::Duration::hours(1))
+            .await
+            .unwrap();
+        let (token, _) = generate_jwt_token(username, role, &session_id).unwrap();
+        (token, session_id)
     }
 
-    #[test]
-    fn test_user_credentials() {
-        assert!(verify_user_credentials(DEFAULT_USERNAME, DEFAULT_PASSWORD));
-        assert!(!verify_user_credentials("wrong_user", "wrong_pass"));
-        assert!(!verify_user_credentials(DEFAULT_USERNAME, "wrong_pass"));
+    #[tokio::test]
+    async fn test_user_credentials() {
+        let db = test_db().await;
+        // Hash SHA-256 d'une version précédente
+        let legacy = "ef92b778bafe771e89245b89ecbc08a44a4e166c06659911881f383d4473e94f";
+        assert!(db.create_first_admin("admin", legacy).await.unwrap());
+        // Le compte initial n'est créé qu'une fois
+        assert!(!db.create_first_admin("root", legacy).await.unwrap());
+
+        let user = verify_user_credentials(&db, "admin", "password123").await.unwrap();
+        assert_eq!(user.map(|user| user.role), Some("admin".to_string()));
+        // Connexion réussie : le hash historique est remplacé par un hash Argon2id
+        let (_, rehashed) = db.find_user_credentials("admin").await.unwrap().unwrap();
+        assert!(rehashed.starts_with("$argon2id$"));
+        assert!(verify_user_credentials(&db, "admin", "password123").await.unwrap().is_some());
+        assert!(verify_user_credentials(&db, "wrong_user", "wrong_pass").await.unwrap().is_none());
+        assert!(verify_user_credentials(&db, "admin", "wrong_pass").await.unwrap().is_none());
+
+        // Un compte désactivé ne peut plus se connecter
+        let update = UserUpdate {
+            enabled: Some(false),
+            ..Default::default()
+        };
+        db.update_user(1, &update, None).await.unwrap();
+        assert!(verify_user_credentials(&db, "admin", "password123").await.unwrap().is_none());
     }
 
     #[test]
     fn test_jwt_token_generation() {
-        let token = generate_jwt_token("test_user", "admin").unwrap();
-        assert!(!token.is_empty());
-        assert!(verify_jwt_token(&token));
+        let (token, expires_at) = generate_jwt_token("test_user", "admin", "session").unwrap();
+        assert!(expires_at > Utc::now().timestamp());
+        let claims = decode_token(&token).unwrap();
+        assert_eq!(claims.sub, "test_user");
+        assert_eq!(claims.role, "admin");
+        assert_eq!(claims.sid, "session");
+        assert!(decode_token("garbage").is_err());
     }
 
-    #[test]
-    fn test_user_extraction_from_token() {
-        let token = generate_jwt_token("test_user", "admin").unwrap();
-        let user_info = extract_user_from_token(&token).unwrap();
-        assert_eq!(user_info.username, "test_user");
-        assert_eq!(user_info.role, "admin");
+    async fn authorize<R: RequiredRole>(db: &Database, token: Option<&str>) -> Result<UserInfo, StatusCode> {
+        let mut request = axum::http::Request::builder().uri("/api/reset");
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        Authorized::<R>::from_request_parts(&mut parts, db)
+            .await
+            .map(|authorized| authorized.user)
+            .map_err(|(status, _)| status)
+    }
+
+    #[tokio::test]
+    async fn test_roles_are_enforced() {
+        assert!(Role::Admin > Role::User && Role::User > Role::Viewer);
+        assert_eq!(Role::parse("viewer"), Some(Role::Viewer));
+        assert_eq!(Role::parse("root"), None);
+
+        let db = test_db().await;
+        assert!(db.create_first_admin("admin", "hash").await.unwrap());
+        let (viewer, _) = session_token(&db, "alice", "viewer").await;
+        let (admin, admin_session) = session_token(&db, "admin", "admin").await;
+
+        assert_eq!(authorize::<Viewer>(&db, Some(&viewer)).await.unwrap().username, "alice");
+        assert_eq!(authorize::<UserRole>(&db, Some(&viewer)).await.unwrap_err(), StatusCode::FORBIDDEN);
+        assert_eq!(authorize::<Admin>(&db, Some(&viewer)).await.unwrap_err(), StatusCode::FORBIDDEN);
+        assert!(authorize::<Admin>(&db, Some(&admin)).await.is_ok());
+
+        assert_eq!(authorize::<Viewer>(&db, None).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+        assert_eq!(authorize::<Viewer>(&db, Some("garbage")).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+
+        // Rôle inconnu : aucun droit
+        let (unknown, _) = session_token(&db, "bob", "root").await;
+        assert_eq!(authorize::<Viewer>(&db, Some(&unknown)).await.unwrap_err(), StatusCode::FORBIDDEN);
+
+        // Session révoquée ou inconnue : le jeton, bien que valide, est refusé
+        db.revoke_session(&admin_session, None, RevokeReason::Logout).await.unwrap();
+        assert_eq!(authorize::<Admin>(&db, Some(&admin)).await.unwrap_err(), StatusCode::UNAUTHORIZED);
+        let (orphan, _) = generate_jwt_token("admin", "admin", "missing").unwrap();
+        assert_eq!(authorize::<Viewer>(&db, Some(&orphan)).await.unwrap_err(), StatusCode::UNAUTHORIZED);
     }
 }
\ No newline at end of file
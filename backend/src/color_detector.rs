@@ -0,0 +1,328 @@
+use image::{imageops::FilterType, DynamicImage, RgbImage};
+
+use crate::detector::{BoundingBox, Detection, DetectionParams, Detector, DetectorInfo};
+
+// Plage HSV au format OpenCV (H: 0-180, S et V: 0-255)
+type HsvRange = ([u8; 3], [u8; 3]);
+
+// Classe de couleur détectable et type d'objet associé
+pub struct ColorClass {
+    pub color: &'static str,
+    pub object_type: &'static str,
+    pub ranges: &'static [HsvRange],
+}
+
+// Mêmes seuils que detection.py
+pub const COLOR_CLASSES: &[ColorClass] = &[
+    ColorClass {
+        color: "red",
+        object_type: "Carte microchip",
+        ranges: &[([0, 120, 70], [10, 255, 255]), ([170, 120, 70], [180, 255, 255])],
+    },
+    ColorClass {
+        color: "green",
+        object_type: "Carte personnalisée",
+        ranges: &[([36, 50, 70], [89, 255, 255])],
+    },
+    ColorClass {
+        color: "blue",
+        object_type: "STM32",
+        ranges: &[([90, 50, 70], [128, 255, 255])],
+    },
+];
+
+// Taille maximale de l'image de travail (comme la capture 640x480 de detection.py)
+const WORKING_MAX_SIDE: u32 = 640;
+// Surface minimale d'un objet en pixels de l'image de travail
+const MIN_BLOB_AREA: usize = 500;
+// Part de l'image à partir de laquelle la confiance vaut 1.0
+const FULL_CONFIDENCE_AREA_RATIO: f32 = 0.02;
+// Rayon du noyau morphologique (5x5 comme detection.py)
+const MORPH_RADIUS: usize = 2;
+
+// Détecteur d'objets par segmentation couleur HSV
+pub struct ColorDetector {
+    min_area: usize,
+}
+
+impl ColorDetector {
+    pub fn new() -> Self {
+        Self {
+            min_area: MIN_BLOB_AREA,
+        }
+    }
+}
+
+impl Detector for ColorDetector {
+    fn name(&self) -> &str {
+        "color"
+    }
+
+    fn describe(&self) -> DetectorInfo {
+        DetectorInfo {
+            name: self.name().to_string(),
+            description: "Segmentation couleur HSV (rouge, vert, bleu)".to_string(),
+            classes: COLOR_CLASSES
+                .iter()
+                .map(|class| class.object_type.to_string())
+                .collect(),
+        }
+    }
+
+    // Détecter les objets colorés, boîtes exprimées dans les coordonnées de l'image d'origine
+    fn detect(&self, image: &DynamicImage, params: &DetectionParams) -> Result<Vec<Detection>, String> {
+        let (orig_width, orig_height) = (image.width(), image.height());
+        if orig_width == 0 || orig_height == 0 {
+            return Ok(Vec::new());
+        }
+
+        let working = if orig_width.max(orig_height) > WORKING_MAX_SIDE {
+            image.resize(WORKING_MAX_SIDE, WORKING_MAX_SIDE, FilterType::Triangle)
+        } else {
+            image.clone()
+        }
+        .to_rgb8();
+
+        let (width, height) = (working.width() as usize, working.height() as usize);
+        let scale_x = orig_width as f32 / width as f32;
+        let scale_y = orig_height as f32 / height as f32;
+        let full_area = (width * height) as f32 * FULL_CONFIDENCE_AREA_RATIO;
+
+        let hsv = to_hsv(&working);
+        let mut detections = Vec::new();
+
+        for class in COLOR_CLASSES {
+            let mask: Vec<bool> = hsv
+                .iter()
+                .map(|pixel| class.ranges.iter().any(|range| in_range(pixel, range)))
+                .collect();
+
+            // Ouverture puis fermeture pour nettoyer le masque
+            let opened = dilate(&erode(&mask, width, height), width, height);
+            let mask = erode(&dilate(&opened, width, height), width, height);
+
+            for blob in connected_components(&mask, width, height) {
+                if blob.area < self.min_area {
+                    continue;
+                }
+
+                let confidence = (blob.area as f32 / full_area).sqrt().min(1.0);
+                if confidence < params.confidence_threshold {
+                    continue;
+                }
+
+                detections.push(Detection {
+                    class: class.object_type.to_string(),
+                    color: Some(class.color.to_string()),
+                    confidence,
+                    bbox: BoundingBox {
+                        x: blob.min_x as f32 * scale_x,
+                        y: blob.min_y as f32 * scale_y,
+                        width: (blob.max_x - blob.min_x + 1) as f32 * scale_x,
+                        height: (blob.max_y - blob.min_y + 1) as f32 * scale_y,
+                    },
+                    track_id: None,
+                });
+            }
+        }
+
+        detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        Ok(detections)
+    }
+}
+
+// Conversion RGB -> HSV selon la convention OpenCV 8 bits
+fn to_hsv(image: &RgbImage) -> Vec<[u8; 3]> {
+    image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b] = pixel.0.map(|c| c as f32);
+            let max = r.max(g).max(b);
+            let min = r.min(g).min(b);
+            let delta = max - min;
+
+            let saturation = if max > 0.0 { delta / max * 255.0 } else { 0.0 };
+            let hue = if delta == 0.0 {
+                0.0
+            } else if max == r {
+                60.0 * ((g - b) / delta)
+            } else if max == g {
+                60.0 * ((b - r) / delta) + 120.0
+            } else {
+                60.0 * ((r - g) / delta) + 240.0
+            };
+            let hue = if hue < 0.0 { hue + 360.0 } else { hue };
+
+            [(hue / 2.0).round() as u8, saturation.round() as u8, max as u8]
+        })
+        .collect()
+}
+
+fn in_range(pixel: &[u8; 3], (lower, upper): &HsvRange) -> bool {
+    (0..3).all(|i| pixel[i] >= lower[i] && pixel[i] <= upper[i])
+}
+
+// Filtre min/max séparable sur un noyau carré
+fn morph(mask: &[bool], width: usize, height: usize, keep_if_all: bool) -> Vec<bool> {
+    let window = |get: &dyn Fn(usize) -> bool, center: usize, len: usize| {
+        let start = center.saturating_sub(MORPH_RADIUS);
+        let end = (center + MORPH_RADIUS).min(len - 1);
+        if keep_if_all {
+            (start..=end).all(get)
+        } else {
+            (start..=end).any(get)
+        }
+    };
+
+    let mut horizontal = vec![false; mask.len()];
+    for y in 0..height {
+        for x in 0..width {
+            horizontal[y * width + x] = window(&|i| mask[y * width + i], x, width);
+        }
+    }
+
+    let mut result = vec![false; mask.len()];
+    for y in 0..height {
+        for x in 0..width {
+            result[y * width + x] = window(&|i| horizontal[i * width + x], y, height);
+        }
+    }
+    result
+}
+
+fn erode(mask: &[bool], width: usize, height: usize) -> Vec<bool> {
+    morph(mask, width, height, true)
+}
+
+fn dilate(mask: &[bool], width: usize, height: usize) -> Vec<bool> {
+    morph(mask, width, height, false)
+}
+
+// Composante connexe du masque
+struct Blob {
+    area: usize,
+    min_x: usize,
+    min_y: usize,
+    max_x: usize,
+    max_y: usize,
+}
+
+// Étiquetage en 8-connexité par parcours en profondeur
+fn connected_components(mask: &[bool], width: usize, height: usize) -> Vec<Blob> {
+    let mut visited = vec![false; mask.len()];
+    let mut blobs = Vec::new();
+    let mut stack = Vec::new();
+
+    for start in 0..mask.len() {
+        if !mask[start] || visited[start] {
+            continue;
+        }
+
+        visited[start] = true;
+        stack.push(start);
+        let mut blob = Blob {
+            area: 0,
+            min_x: width,
+            min_y: height,
+            max_x: 0,
+            max_y: 0,
+        };
+
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % width, index / width);
+            blob.area += 1;
+            blob.min_x = blob.min_x.min(x);
+            blob.min_y = blob.min_y.min(y);
+            blob.max_x = blob.max_x.max(x);
+            blob.max_y = blob.max_y.max(y);
+
+            for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                    let neighbor = ny * width + nx;
+                    if mask[neighbor] && !visited[neighbor] {
+                        visited[neighbor] = true;
+                        stack.push(neighbor);
+                    }
+                }
+            }
+        }
+
+        blobs.push(blob);
+    }
+
+    blobs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn detect(image: &DynamicImage, confidence_threshold: f32) -> Vec<Detection> {
+        ColorDetector::new()
+            .detect(image, &DetectionParams { confidence_threshold })
+            .unwrap()
+    }
+
+    fn image_with_squares(squares: &[(u32, u32, u32, [u8; 3])]) -> DynamicImage {
+        let mut image = RgbImage::from_pixel(400, 300, Rgb([230, 230, 230]));
+        for &(x0, y0, size, color) in squares {
+            for y in y0..y0 + size {
+                for x in x0..x0 + size {
+                    image.put_pixel(x, y, Rgb(color));
+                }
+            }
+        }
+        DynamicImage::ImageRgb8(image)
+    }
+
+    #[test]
+    fn test_hsv_conversion_matches_opencv() {
+        let image = RgbImage::from_fn(3, 1, |x, _| match x {
+            0 => Rgb([255, 0, 0]),
+            1 => Rgb([0, 255, 0]),
+            _ => Rgb([0, 0, 255]),
+        });
+        assert_eq!(to_hsv(&image), vec![[0, 255, 255], [60, 255, 255], [120, 255, 255]]);
+    }
+
+    #[test]
+    fn test_detects_each_color_with_bbox() {
+        let image = image_with_squares(&[
+            (10, 10, 40, [220, 20, 20]),
+            (80, 20, 30, [20, 200, 40]),
+            (140, 90, 50, [20, 40, 220]),
+        ]);
+        let detections = detect(&image, 0.0);
+        assert_eq!(detections.len(), 3);
+
+        let red = detections.iter().find(|d| d.color.as_deref() == Some("red")).unwrap();
+        assert_eq!(red.class, "Carte microchip");
+        assert_eq!((red.bbox.x, red.bbox.y, red.bbox.width, red.bbox.height), (10.0, 10.0, 40.0, 40.0));
+
+        let blue = detections.iter().find(|d| d.color.as_deref() == Some("blue")).unwrap();
+        assert_eq!(blue.class, "STM32");
+        assert!(blue.confidence > red.confidence);
+    }
+
+    #[test]
+    fn test_ignores_small_blobs_and_low_confidence() {
+        let image = image_with_squares(&[(10, 10, 15, [220, 20, 20]), (100, 50, 40, [20, 200, 40])]);
+        assert_eq!(detect(&image, 0.0).len(), 1);
+        assert!(detect(&image, 1.01).is_empty());
+    }
+
+    #[test]
+    fn test_boxes_scaled_back_to_original_size() {
+        let mut image = RgbImage::from_pixel(1280, 960, Rgb([230, 230, 230]));
+        for y in 100..300 {
+            for x in 200..600 {
+                image.put_pixel(x, y, Rgb([20, 40, 220]));
+            }
+        }
+        let detections = detect(&DynamicImage::ImageRgb8(image), 0.0);
+        assert_eq!(detections.len(), 1);
+        let bbox = &detections[0].bbox;
+        assert!((bbox.x - 200.0).abs() <= 2.0 && (bbox.width - 400.0).abs() <= 4.0);
+    }
+}
@@ -0,0 +1,391 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::detector::{BoundingBox, Detection};
+use crate::tracker::{TrackEvent, TrackEventKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    // Polygone : entrée et sortie des objets
+    Zone,
+    // Segment : franchissement dans un sens donné
+    Line,
+}
+
+impl RuleKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RuleKind::Zone => "zone",
+            RuleKind::Line => "line",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "zone" => Some(RuleKind::Zone),
+            "line" => Some(RuleKind::Line),
+            _ => None,
+        }
+    }
+}
+
+// Sens compté pour une ligne A→B : "in" quand l'objet passe du côté gauche au côté droit du segment
+// (vers le bas pour une ligne tracée de gauche à droite)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineDirection {
+    In,
+    Out,
+    #[default]
+    Both,
+}
+
+impl LineDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineDirection::In => "in",
+            LineDirection::Out => "out",
+            LineDirection::Both => "both",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "in" => Some(LineDirection::In),
+            "out" => Some(LineDirection::Out),
+            "both" => Some(LineDirection::Both),
+            _ => None,
+        }
+    }
+
+    fn accepts(self, event: CountingEventKind) -> bool {
+        match self {
+            LineDirection::Both => true,
+            LineDirection::In => event == CountingEventKind::In,
+            LineDirection::Out => event == CountingEventKind::Out,
+        }
+    }
+}
+
+// Point en pixels de l'image
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Point {
+    fn center(bbox: &BoundingBox) -> Self {
+        Self {
+            x: bbox.x + bbox.width / 2.0,
+            y: bbox.y + bbox.height / 2.0,
+        }
+    }
+}
+
+// Règle de comptage d'une caméra, table counting_rules
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountingRule {
+    pub id: i64,
+    pub g_id: String,
+    pub name: String,
+    pub kind: RuleKind,
+    pub points: Vec<Point>,
+    pub direction: LineDirection,
+    pub created_at: String,
+}
+
+// Corps de POST /api/rules
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewCountingRule {
+    pub g_id: String,
+    pub name: String,
+    pub kind: RuleKind,
+    pub points: Vec<Point>,
+    #[serde(default)]
+    pub direction: LineDirection,
+}
+
+impl NewCountingRule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.g_id.trim().is_empty() || self.name.trim().is_empty() {
+            return Err("g_id and name are required".to_string());
+        }
+        if self.points.iter().any(|point| !point.x.is_finite() || !point.y.is_finite()) {
+            return Err("Points must be finite numbers".to_string());
+        }
+        match self.kind {
+            RuleKind::Zone if self.points.len() < 3 => Err("A zone needs at least 3 points".to_string()),
+            RuleKind::Line if self.points.len() != 2 || self.points[0] == self.points[1] => {
+                Err("A line needs exactly 2 distinct points".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CountingEventKind {
+    Enter,
+    Leave,
+    In,
+    Out,
+}
+
+impl CountingEventKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CountingEventKind::Enter => "enter",
+            CountingEventKind::Leave => "leave",
+            CountingEventKind::In => "in",
+            CountingEventKind::Out => "out",
+        }
+    }
+}
+
+// Événement de comptage, stocké dans counting_events et agrégé dans daily_stats
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CountingEvent {
+    pub rule_id: i64,
+    pub rule: String,
+    pub event: CountingEventKind,
+    pub track_id: u64,
+    pub class: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+// Dernière position connue d'une piste et zones dans lesquelles elle se trouve
+#[derive(Debug, Clone)]
+struct TrackPosition {
+    center: Point,
+    class: String,
+    color: Option<String>,
+    zones: Vec<i64>,
+}
+
+impl TrackPosition {
+    fn event(&self, rule: &CountingRule, event: CountingEventKind, track_id: u64) -> CountingEvent {
+        CountingEvent {
+            rule_id: rule.id,
+            rule: rule.name.clone(),
+            event,
+            track_id,
+            class: self.class.clone(),
+            color: self.color.clone(),
+        }
+    }
+}
+
+// État du comptage d'une caméra, mis à jour à chaque image après le tracker
+#[derive(Debug, Default)]
+pub struct CountingState {
+    tracks: HashMap<u64, TrackPosition>,
+}
+
+impl CountingState {
+    pub fn update(
+        &mut self,
+        rules: &[CountingRule],
+        detections: &[Detection],
+        track_events: &[TrackEvent],
+    ) -> Vec<CountingEvent> {
+        let mut events = Vec::new();
+
+        for detection in detections {
+            let Some(track_id) = detection.track_id else {
+                continue;
+            };
+            let center = Point::center(&detection.bbox);
+            let previous = self.tracks.remove(&track_id);
+            let mut position = TrackPosition {
+                center,
+                class: detection.class.clone(),
+                color: detection.color.clone(),
+                zones: Vec::new(),
+            };
+
+            for rule in rules {
+                match rule.kind {
+                    RuleKind::Zone => {
+                        let inside = contains(&rule.points, center);
+                        let was_inside = previous.as_ref().is_some_and(|previous| previous.zones.contains(&rule.id));
+                        if inside {
+                            position.zones.push(rule.id);
+                        }
+                        if inside && !was_inside {
+                            events.push(position.event(rule, CountingEventKind::Enter, track_id));
+                        } else if was_inside && !inside {
+                            events.push(position.event(rule, CountingEventKind::Leave, track_id));
+                        }
+                    }
+                    RuleKind::Line => {
+                        let crossing = previous
+                            .as_ref()
+                            .and_then(|previous| crossing(rule.points[0], rule.points[1], previous.center, center));
+                        if let Some(event) = crossing.filter(|event| rule.direction.accepts(*event)) {
+                            events.push(position.event(rule, event, track_id));
+                        }
+                    }
+                }
+            }
+
+            self.tracks.insert(track_id, position);
+        }
+
+        // Une piste qui disparaît dans une zone en sort
+        for exit in track_events.iter().filter(|event| event.event == TrackEventKind::Exit) {
+            let Some(position) = self.tracks.remove(&exit.track_id) else {
+                continue;
+            };
+            for rule in rules.iter().filter(|rule| position.zones.contains(&rule.id)) {
+                events.push(position.event(rule, CountingEventKind::Leave, exit.track_id));
+            }
+        }
+
+        events
+    }
+}
+
+// Produit vectoriel (B - A) × (P - A) : signe du côté de P par rapport à A→B
+fn side(a: Point, b: Point, p: Point) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// Point dans le polygone (lancer de rayon)
+fn contains(polygon: &[Point], p: Point) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[j]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < (b.x - a.x) * (p.y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+// Franchissement du segment A→B par le déplacement from→to ; "in" de gauche à droite
+fn crossing(a: Point, b: Point, from: Point, to: Point) -> Option<CountingEventKind> {
+    let (before, after) = (side(a, b, from) < 0.0, side(a, b, to) < 0.0);
+    if before == after {
+        return None;
+    }
+    // Le déplacement doit couper le segment lui-même, pas son prolongement
+    if side(from, to, a) * side(from, to, b) > 0.0 {
+        return None;
+    }
+    Some(if before {
+        CountingEventKind::In
+    } else {
+        CountingEventKind::Out
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: i64, kind: RuleKind, points: &[(f32, f32)], direction: LineDirection) -> CountingRule {
+        CountingRule {
+            id,
+            g_id: "CAM_1".to_string(),
+            name: format!("rule_{}", id),
+            kind,
+            points: points.iter().map(|&(x, y)| Point { x, y }).collect(),
+            direction,
+            created_at: String::new(),
+        }
+    }
+
+    fn tracked(track_id: u64, x: f32, y: f32) -> Detection {
+        Detection {
+            class: "STM32".to_string(),
+            color: None,
+            confidence: 0.9,
+            bbox: BoundingBox {
+                x: x - 5.0,
+                y: y - 5.0,
+                width: 10.0,
+                height: 10.0,
+            },
+            track_id: Some(track_id),
+        }
+    }
+
+    fn kinds(events: &[CountingEvent]) -> Vec<(i64, CountingEventKind)> {
+        events.iter().map(|event| (event.rule_id, event.event)).collect()
+    }
+
+    #[test]
+    fn test_counts_line_crossings_by_direction() {
+        // Ligne horizontale à mi-hauteur, tracée de gauche à droite
+        let rules = [
+            rule(1, RuleKind::Line, &[(0.0, 100.0), (200.0, 100.0)], LineDirection::Both),
+            rule(2, RuleKind::Line, &[(0.0, 100.0), (200.0, 100.0)], LineDirection::Out),
+        ];
+        let mut state = CountingState::default();
+
+        assert!(state.update(&rules, &[tracked(1, 50.0, 80.0)], &[]).is_empty());
+        let events = state.update(&rules, &[tracked(1, 50.0, 120.0)], &[]);
+        assert_eq!(kinds(&events), vec![(1, CountingEventKind::In)]);
+
+        let events = state.update(&rules, &[tracked(1, 50.0, 90.0)], &[]);
+        assert_eq!(
+            kinds(&events),
+            vec![(1, CountingEventKind::Out), (2, CountingEventKind::Out)]
+        );
+
+        // Passage à côté du segment : pas de franchissement
+        state.update(&rules, &[tracked(2, 250.0, 80.0)], &[]);
+        assert!(state.update(&rules, &[tracked(2, 250.0, 120.0)], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_reports_zone_enter_and_leave() {
+        let rules = [rule(
+            1,
+            RuleKind::Zone,
+            &[(0.0, 0.0), (100.0, 0.0), (100.0, 100.0), (0.0, 100.0)],
+            LineDirection::Both,
+        )];
+        let mut state = CountingState::default();
+
+        assert!(state.update(&rules, &[tracked(1, 150.0, 50.0)], &[]).is_empty());
+        let events = state.update(&rules, &[tracked(1, 50.0, 50.0)], &[]);
+        assert_eq!(kinds(&events), vec![(1, CountingEventKind::Enter)]);
+        assert!(state.update(&rules, &[tracked(1, 60.0, 50.0)], &[]).is_empty());
+
+        // La piste disparaît dans la zone : sortie
+        let exit = TrackEvent {
+            event: TrackEventKind::Exit,
+            track_id: 1,
+            class: "STM32".to_string(),
+            bbox: tracked(1, 60.0, 50.0).bbox,
+        };
+        let events = state.update(&rules, &[], &[exit]);
+        assert_eq!(kinds(&events), vec![(1, CountingEventKind::Leave)]);
+    }
+
+    #[test]
+    fn test_validates_rules() {
+        let new_rule = |kind, points: Vec<Point>| NewCountingRule {
+            g_id: "CAM_1".to_string(),
+            name: "ligne".to_string(),
+            kind,
+            points,
+            direction: LineDirection::Both,
+        };
+        let point = |x, y| Point { x, y };
+
+        assert!(new_rule(RuleKind::Line, vec![point(0.0, 0.0), point(1.0, 0.0)]).validate().is_ok());
+        assert!(new_rule(RuleKind::Line, vec![point(0.0, 0.0), point(0.0, 0.0)]).validate().is_err());
+        assert!(new_rule(RuleKind::Zone, vec![point(0.0, 0.0), point(1.0, 0.0)]).validate().is_err());
+        assert!(new_rule(RuleKind::Zone, vec![point(0.0, 0.0), point(1.0, 0.0), point(f32::NAN, 1.0)])
+            .validate()
+            .is_err());
+    }
+}
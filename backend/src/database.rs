@@ -1,56 +1,40 @@
-use sqlx::sqlite::SqlitePool;
-use sqlx::Row;
+use sqlx::sqlite::{SqlitePool, SqliteRow};
+use sqlx::{QueryBuilder, Row, Sqlite};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{NaiveDate, NaiveDateTime};
+use futures_util::TryStreamExt;
+use std::collections::HashMap;
+use tokio::sync::mpsc;
 
-// Fonction pour créer la base de données et les tables
-pub async fn create_database() -> Result<SqlitePool, sqlx::Error> {
+use crate::counting::{CountingEvent, CountingRule, LineDirection, NewCountingRule, RuleKind};
+use crate::detector::BoundingBox;
+use crate::devices::{self, Device, DeviceStatus, DeviceStatusChange, DeviceUpdate, DevicesHealth, NewDevice};
+use crate::postprocess::iou;
+use crate::auth::Role;
+use crate::sessions::{RefreshOutcome, RevokeReason, Session};
+use crate::users::{NewUser, User, UserUpdate};
+use crate::migrations::{self, MigrationError};
+
+// Fonction pour ouvrir la base de données et appliquer les migrations
+pub async fn create_database() -> Result<SqlitePool, MigrationError> {
     // Créer le répertoire data s'il n'existe pas
     std::fs::create_dir_all("data").map_err(|e| {
-        sqlx::Error::Io(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Failed to create data directory: {}", e)
-        ))
+        sqlx::Error::Io(std::io::Error::other(format!(
+            "Failed to create data directory: {}",
+            e
+        )))
     })?;
     
-    let pool = SqlitePool::connect("sqlite:data/detection.db").await?;
-
-    // Créer les tables si elles n'existent pas
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS detection_requests (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            g_id TEXT NOT NULL,
-            request_id TEXT NOT NULL UNIQUE,
-            image_data TEXT NOT NULL,
-            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-            status TEXT DEFAULT 'pending'
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
-
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS detections (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            request_id TEXT NOT NULL,
-            g_id TEXT NOT NULL,
-            detected_objects TEXT NOT NULL,
-            confidence_scores TEXT NOT NULL,
-            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (request_id) REFERENCES detection_requests (request_id)
-        )
-        "#,
-    )
-    .execute(&pool)
-    .await?;
+    let pool = SqlitePool::connect("sqlite:data/detection.db?mode=rwc").await?;
+
+    migrations::run(&pool).await?;
 
     Ok(pool)
 }
 
 // Structure pour les requêtes de détection
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DetectionRequest {
     pub id: Option<i64>,
@@ -61,15 +45,232 @@ pub struct DetectionRequest {
     pub status: Option<String>,
 }
 
-// Structure pour les résultats de détection
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Detection {
-    pub id: Option<i64>,
+// Objet détecté, une ligne de detected_objects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectedObject {
+    pub class: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub confidence: f32,
+    // Absente pour les détections saisies manuellement
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<BoundingBox>,
+    // Nombre d'observations fusionnées dans cet objet
+    #[serde(default = "first_sighting")]
+    pub ref_count: i64,
+    // Piste du tracker ayant produit l'objet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<i64>,
+}
+
+fn first_sighting() -> i64 {
+    1
+}
+
+impl From<&crate::detector::Detection> for DetectedObject {
+    fn from(detection: &crate::detector::Detection) -> Self {
+        Self {
+            class: detection.class.clone(),
+            color: detection.color.clone(),
+            confidence: detection.confidence,
+            bbox: Some(detection.bbox.clone()),
+            ref_count: 1,
+            track_id: detection.track_id.map(|id| id as i64),
+        }
+    }
+}
+
+// Résultat de détection ; type/color/confidence décrivent l'objet le plus sûr
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DetectionRecord {
+    pub id: i64,
     pub request_id: String,
     pub g_id: String,
-    pub detected_objects: String,
-    pub confidence_scores: String,
-    pub timestamp: Option<String>,
+    #[serde(rename = "type")]
+    pub object_type: String,
+    pub color: String,
+    pub confidence: f32,
+    pub datetime: String,
+    pub ref_count: i64,
+    // Appareil ayant envoyé la détection (clé d'API)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<i64>,
+    pub objects: Vec<DetectedObject>,
+}
+
+// Nombre de détections d'un type sur une journée
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyCadence {
+    pub day: String,
+    #[serde(rename = "type")]
+    pub object_type: String,
+    pub count: i64,
+}
+
+// Paramètres de GET /api/stats/daily
+#[derive(Debug, Default, Deserialize)]
+pub struct DailyStatsQuery {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub g_id: Option<String>,
+    // Liste séparée par des virgules parmi type, color, g_id, event (défaut : type)
+    pub group_by: Option<String>,
+    // Nom d'une règle de comptage : ses événements remplacent les détections brutes
+    pub rule: Option<String>,
+}
+
+impl DailyStatsQuery {
+    // Colonnes de regroupement en plus du jour
+    pub fn group_columns(&self) -> Result<Vec<&'static str>, String> {
+        let Some(group_by) = self.group_by.as_deref() else {
+            return Ok(vec!["type"]);
+        };
+
+        let mut columns = Vec::new();
+        for name in group_by.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let column = match name {
+                "type" => "type",
+                "color" => "color",
+                "g_id" => "g_id",
+                "event" => "event",
+                "day" => continue,
+                _ => return Err(format!("Invalid group_by value: {}", name)),
+            };
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+        Ok(columns)
+    }
+}
+
+// Agrégat de daily_stats ; seules les colonnes regroupées sont renseignées
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyStat {
+    pub day: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub g_id: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub object_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    pub cadence: i64,
+}
+
+// Nombre maximal d'intervalles d'une série de débit
+const MAX_BUCKETS: i64 = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    #[default]
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Interval {
+    // Format strftime du début d'intervalle
+    fn bucket_format(self) -> &'static str {
+        match self {
+            Interval::Minute => "%Y-%m-%d %H:%M:00",
+            Interval::Hour => "%Y-%m-%d %H:00:00",
+            Interval::Day => "%Y-%m-%d 00:00:00",
+        }
+    }
+
+    // Modificateur de date SQLite pour passer à l'intervalle suivant
+    fn step(self) -> &'static str {
+        match self {
+            Interval::Minute => "+1 minute",
+            Interval::Hour => "+1 hour",
+            Interval::Day => "+1 day",
+        }
+    }
+
+    fn duration(self) -> chrono::Duration {
+        match self {
+            Interval::Minute => chrono::Duration::minutes(1),
+            Interval::Hour => chrono::Duration::hours(1),
+            Interval::Day => chrono::Duration::days(1),
+        }
+    }
+
+    // Période affichée par défaut : une heure, un jour ou un mois
+    fn default_span(self) -> chrono::Duration {
+        match self {
+            Interval::Minute => chrono::Duration::hours(1),
+            Interval::Hour => chrono::Duration::days(1),
+            Interval::Day => chrono::Duration::days(30),
+        }
+    }
+}
+
+// Paramètres de GET /api/stats/throughput (dates en UTC, comme les timestamps SQLite)
+#[derive(Debug, Default, Deserialize)]
+pub struct ThroughputQuery {
+    #[serde(default)]
+    pub interval: Interval,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub g_id: Option<String>,
+    #[serde(rename = "type")]
+    pub object_type: Option<String>,
+}
+
+// Accepte "2024-01-15", "2024-01-15T10:30" ou "2024-01-15 10:30:00"
+fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+}
+
+impl ThroughputQuery {
+    // Bornes [from, to] de la série, validées
+    pub fn range(&self) -> Result<(NaiveDateTime, NaiveDateTime), String> {
+        let parse = |value: &Option<String>, name: &str| {
+            value
+                .as_deref()
+                .map(|value| parse_datetime(value).ok_or(format!("Invalid {} date: {}", name, value)))
+                .transpose()
+        };
+
+        let to = parse(&self.to, "to")?.unwrap_or_else(|| chrono::Utc::now().naive_utc());
+        let from = parse(&self.from, "from")?.unwrap_or(to - self.interval.default_span());
+        if from > to {
+            return Err("from must be before to".to_string());
+        }
+
+        let buckets = (to - from).num_seconds() / self.interval.duration().num_seconds();
+        if buckets >= MAX_BUCKETS {
+            return Err(format!("Too many buckets ({}), use a larger interval", buckets + 1));
+        }
+        Ok((from, to))
+    }
+}
+
+// Nombre d'objets d'un type, un compteur par intervalle
+#[derive(Debug, Clone, Serialize)]
+pub struct ThroughputSeries {
+    #[serde(rename = "type")]
+    pub object_type: String,
+    pub counts: Vec<i64>,
+    pub total: i64,
+}
+
+// Série temporelle prête à tracer : counts[i] correspond à buckets[i]
+#[derive(Debug, Clone, Serialize)]
+pub struct Throughput {
+    pub interval: Interval,
+    pub buckets: Vec<String>,
+    pub series: Vec<ThroughputSeries>,
 }
 
 // Structure pour les statistiques
@@ -77,18 +278,349 @@ pub struct Detection {
 pub struct DetectionStats {
     pub today_count: i64,
     pub total_count: i64,
-    pub recent_detections: Vec<Value>,
+    pub recent_detections: Vec<DetectionRecord>,
+}
+
+// Nombre de résultats par page de l'historique
+const DEFAULT_PAGE_SIZE: i64 = 100;
+const MAX_PAGE_SIZE: i64 = 1000;
+// Détections en attente d'écriture pendant un export
+const EXPORT_BUFFER: usize = 64;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortField {
+    #[default]
+    Datetime,
+    Confidence,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// Filtres, tri et pagination de GET /api/detections
+#[derive(Debug, Default, Deserialize)]
+pub struct DetectionQuery {
+    pub g_id: Option<String>,
+    pub from_date: Option<NaiveDate>,
+    pub to_date: Option<NaiveDate>,
+    pub color: Option<String>,
+    #[serde(rename = "type")]
+    pub object_type: Option<String>,
+    pub min_confidence: Option<f32>,
+    pub status: Option<String>,
+    pub device_id: Option<i64>,
+    #[serde(default)]
+    pub sort: SortField,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+}
+
+impl DetectionQuery {
+    fn page_size(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+
+    // Décoder le curseur opaque renvoyé par la page précédente
+    pub fn page_cursor(&self) -> Result<Option<PageCursor>, String> {
+        let Some(cursor) = &self.cursor else {
+            return Ok(None);
+        };
+
+        let decoded = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or("Invalid cursor")?;
+        let (value, id) = decoded.rsplit_once('|').ok_or("Invalid cursor")?;
+        let id = id.parse().map_err(|_| "Invalid cursor")?;
+        if self.sort == SortField::Confidence && value.parse::<f32>().is_err() {
+            return Err("Cursor does not match the sort field".to_string());
+        }
+
+        Ok(Some(PageCursor {
+            value: value.to_string(),
+            id,
+        }))
+    }
+}
+
+// Position dans l'historique : valeur de tri et id du dernier résultat renvoyé
+#[derive(Debug, Clone)]
+pub struct PageCursor {
+    value: String,
+    id: i64,
+}
+
+impl PageCursor {
+    fn after(record: &DetectionRecord, sort: SortField) -> Self {
+        let value = match sort {
+            SortField::Datetime => record.datetime.clone(),
+            SortField::Confidence => record.confidence.to_string(),
+        };
+        Self { value, id: record.id }
+    }
+
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(format!("{}|{}", self.value, self.id))
+    }
+}
+
+// Page de résultats ; next_cursor est absent sur la dernière page
+#[derive(Debug, Serialize)]
+pub struct DetectionPage {
+    pub items: Vec<DetectionRecord>,
+    pub total: i64,
+    pub next_cursor: Option<String>,
+}
+
+// Ajouter les filtres de la requête (la table detections est aliasée d)
+fn push_filters(builder: &mut QueryBuilder<'_, Sqlite>, query: &DetectionQuery) {
+    if let Some(g_id) = &query.g_id {
+        builder.push(" AND d.g_id = ").push_bind(g_id.clone());
+    }
+    if let Some(from_date) = query.from_date {
+        builder.push(" AND d.timestamp >= ").push_bind(from_date.to_string());
+    }
+    if let Some(to_date) = query.to_date {
+        let end = to_date.succ_opt().unwrap_or(to_date);
+        builder.push(" AND d.timestamp < ").push_bind(end.to_string());
+    }
+    if let Some(status) = &query.status {
+        builder
+            .push(" AND EXISTS (SELECT 1 FROM detection_requests r WHERE r.request_id = d.request_id AND r.status = ")
+            .push_bind(status.clone())
+            .push(")");
+    }
+    if let Some(device_id) = query.device_id {
+        builder
+            .push(" AND EXISTS (SELECT 1 FROM detection_requests r WHERE r.request_id = d.request_id AND r.device_id = ")
+            .push_bind(device_id)
+            .push(")");
+    }
+
+    // Les critères sur les objets doivent être vérifiés par un même objet
+    if query.color.is_some() || query.object_type.is_some() || query.min_confidence.is_some() {
+        builder.push(" AND EXISTS (SELECT 1 FROM detected_objects f WHERE f.request_id = d.request_id");
+        if let Some(color) = &query.color {
+            builder.push(" AND f.color = ").push_bind(color.clone());
+        }
+        if let Some(object_type) = &query.object_type {
+            builder.push(" AND f.class = ").push_bind(object_type.clone());
+        }
+        if let Some(min_confidence) = query.min_confidence {
+            builder.push(" AND f.confidence >= ").push_bind(min_confidence);
+        }
+        builder.push(")");
+    }
+}
+
+// Colonnes communes aux requêtes qui renvoient des DetectionRecord
+const RECORD_SELECT: &str = "
+    SELECT d.id, d.request_id, d.g_id, d.timestamp,
+           COALESCE(o.class, '') as type,
+           COALESCE(o.color, '') as color,
+           COALESCE(o.confidence, 0) as confidence,
+           COALESCE(o.ref_count, 1) as ref_count,
+           r.device_id
+    FROM detections d
+    LEFT JOIN detection_requests r ON r.request_id = d.request_id
+    LEFT JOIN detected_objects o ON o.id = (
+        SELECT id FROM detected_objects
+        WHERE request_id = d.request_id
+        ORDER BY confidence DESC, id
+        LIMIT 1
+    )";
+
+fn record_from_row(row: &SqliteRow) -> DetectionRecord {
+    DetectionRecord {
+        id: row.get("id"),
+        request_id: row.get("request_id"),
+        g_id: row.get("g_id"),
+        object_type: row.get("type"),
+        color: row.get("color"),
+        confidence: row.get("confidence"),
+        datetime: row.get("timestamp"),
+        ref_count: row.get("ref_count"),
+        device_id: row.get("device_id"),
+        objects: Vec::new(),
+    }
+}
+
+// prefix permet de lire les colonnes d'objet aliasées dans une jointure
+fn object_from_row(row: &SqliteRow, prefix: &str) -> DetectedObject {
+    let column = |name: &str| format!("{}{}", prefix, name);
+    let bbox = match (
+        row.get::<Option<f32>, _>(column("bbox_x").as_str()),
+        row.get::<Option<f32>, _>(column("bbox_y").as_str()),
+        row.get::<Option<f32>, _>(column("bbox_width").as_str()),
+        row.get::<Option<f32>, _>(column("bbox_height").as_str()),
+    ) {
+        (Some(x), Some(y), Some(width), Some(height)) => Some(BoundingBox { x, y, width, height }),
+        _ => None,
+    };
+
+    DetectedObject {
+        class: row.get(column("class").as_str()),
+        color: row.get(column("color").as_str()),
+        confidence: row.get(column("confidence").as_str()),
+        bbox,
+        ref_count: row.get(column("ref_count").as_str()),
+        track_id: row.get(column("track_id").as_str()),
+    }
+}
+
+const DEVICE_COLUMNS: &str = "id, name, location, type, enabled, key_prefix, status, last_seen_at, created_at";
+
+fn device_from_row(row: &SqliteRow) -> Device {
+    Device {
+        id: row.get("id"),
+        name: row.get("name"),
+        location: row.get("location"),
+        device_type: row.get("type"),
+        enabled: row.get("enabled"),
+        key_prefix: row.get("key_prefix"),
+        status: DeviceStatus::parse(row.get("status")),
+        last_seen_at: row.get("last_seen_at"),
+        created_at: row.get("created_at"),
+    }
+}
+
+const USER_COLUMNS: &str = "id, username, role, enabled, created_at, updated_at";
+
+fn user_from_row(row: &SqliteRow) -> User {
+    User {
+        id: row.get("id"),
+        username: row.get("username"),
+        role: row.get("role"),
+        enabled: row.get("enabled"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    }
+}
+
+const SESSION_SELECT: &str = "SELECT s.id, s.user_id, u.username, s.user_agent, s.created_at, s.last_used_at, s.expires_at
+    FROM sessions s JOIN users u ON u.id = s.user_id";
+
+fn session_from_row(row: &SqliteRow) -> Session {
+    Session {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        user_agent: row.get("user_agent"),
+        created_at: row.get("created_at"),
+        last_used_at: row.get("last_used_at"),
+        expires_at: row.get("expires_at"),
+        current: false,
+    }
+}
+
+// Décalage relatif à maintenant pour datetime('now', ?)
+fn from_now(ttl: chrono::Duration) -> String {
+    format!("+{} seconds", ttl.num_seconds())
+}
+
+// Règle stockée ; les lignes illisibles (points corrompus) sont ignorées
+fn rule_from_row(row: &SqliteRow) -> Option<CountingRule> {
+    Some(CountingRule {
+        id: row.get("id"),
+        g_id: row.get("g_id"),
+        name: row.get("name"),
+        kind: RuleKind::parse(row.get("kind"))?,
+        points: serde_json::from_str(row.get("points")).ok()?,
+        direction: LineDirection::parse(row.get("direction"))?,
+        created_at: row.get("created_at"),
+    })
+}
+
+// Sens du tri et comparaison à appliquer au curseur
+fn sort_direction(order: SortOrder) -> (&'static str, &'static str) {
+    match order {
+        SortOrder::Desc => ("DESC", "<"),
+        SortOrder::Asc => ("ASC", ">"),
+    }
+}
+
+// Fusion des observations répétées d'un même objet (même classe, même zone, fenêtre de temps)
+#[derive(Debug, Clone, Copy)]
+pub struct DedupConfig {
+    // 0 désactive la fusion
+    pub window_secs: u64,
+    pub iou_threshold: f32,
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            window_secs: 10,
+            iou_threshold: 0.5,
+        }
+    }
+}
+
+impl DedupConfig {
+    // Surchargeable via DEDUP_WINDOW_SECS et DEDUP_IOU
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let env = |name: &str| std::env::var(name).ok();
+        Self {
+            window_secs: env("DEDUP_WINDOW_SECS")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default.window_secs),
+            iou_threshold: env("DEDUP_IOU")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default.iou_threshold),
+        }
+    }
+
+    // Deux pistes différentes sont deux objets. Une même piste ne suffit pas : les identifiants
+    // repartent de 1 quand le tracker est recréé, les boîtes doivent donc aussi se recouvrir.
+    // Sans boîte (saisie manuelle), rien ne prouve qu'il s'agit du même objet : pas de fusion.
+    fn same_object(&self, stored: &DetectedObject, incoming: &DetectedObject) -> bool {
+        if let (Some(stored), Some(incoming)) = (stored.track_id, incoming.track_id) {
+            if stored != incoming {
+                return false;
+            }
+        }
+        match (&stored.bbox, &incoming.bbox) {
+            (Some(a), Some(b)) => iou(a, b) >= self.iou_threshold,
+            _ => false,
+        }
+    }
+}
+
+// Résultat d'un enregistrement : nouvelle détection et/ou détections existantes mises à jour
+#[derive(Debug, Clone, Default)]
+pub struct InsertOutcome {
+    pub detection_id: Option<i64>,
+    pub merged_into: Vec<i64>,
 }
 
 // Structure principale pour la base de données
 #[derive(Clone)]
 pub struct Database {
     pub pool: SqlitePool,
+    dedup: DedupConfig,
 }
 
 impl Database {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            dedup: DedupConfig::default(),
+        }
+    }
+
+    pub fn with_dedup(mut self, dedup: DedupConfig) -> Self {
+        self.dedup = dedup;
+        self
     }
 
     // Insérer une nouvelle requête de détection
@@ -97,134 +629,1645 @@ impl Database {
         g_id: &str,
         request_id: &str,
         image_data: &str,
+        device_id: Option<i64>,
     ) -> Result<(), sqlx::Error> {
         sqlx::query(
-            "INSERT INTO detection_requests (g_id, request_id, image_data) VALUES (?, ?, ?)"
+            "INSERT INTO detection_requests (g_id, request_id, image_data, device_id) VALUES (?, ?, ?, ?)"
         )
         .bind(g_id)
         .bind(request_id)
         .bind(image_data)
+        .bind(device_id)
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
-    // Insérer un résultat de détection
-    pub async fn insert_detection(
+    // Mettre à jour le statut d'une requête (pending, done, failed)
+    pub async fn update_request_status(&self, request_id: &str, status: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE detection_requests SET status = ? WHERE request_id = ?")
+            .bind(status)
+            .bind(request_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    // Chercher un objet récent auquel rattacher une nouvelle observation
+    async fn find_recent_sighting(
         &self,
+        tx: &mut sqlx::SqliteConnection,
         request_id: &str,
         g_id: &str,
-        detected_objects: &str,
-        confidence_scores: &str,
-    ) -> Result<(), sqlx::Error> {
-        sqlx::query(
-            "INSERT INTO detections (request_id, g_id, detected_objects, confidence_scores) VALUES (?, ?, ?, ?)"
+        object: &DetectedObject,
+    ) -> Result<Option<(i64, i64)>, sqlx::Error> {
+        let candidates = sqlx::query(
+            "SELECT o.*, d.id as detection_id
+             FROM detected_objects o
+             JOIN detections d ON d.request_id = o.request_id
+             WHERE d.g_id = ? AND o.class = ? AND COALESCE(o.color, '') = ? AND o.request_id != ?
+               AND o.last_seen >= datetime('now', ?)
+             ORDER BY o.last_seen DESC
+             LIMIT 20"
         )
-        .bind(request_id)
         .bind(g_id)
-        .bind(detected_objects)
-        .bind(confidence_scores)
-        .execute(&self.pool)
+        .bind(&object.class)
+        .bind(object.color.as_deref().unwrap_or(""))
+        .bind(request_id)
+        .bind(format!("-{} seconds", self.dedup.window_secs))
+        .fetch_all(&mut *tx)
         .await?;
 
-        Ok(())
+        Ok(candidates.iter().find_map(|row| {
+            self.dedup
+                .same_object(&object_from_row(row, ""), object)
+                .then(|| (row.get("id"), row.get("detection_id")))
+        }))
     }
 
-    // Récupérer les détections par g_id
-    pub async fn get_detections_by_gid(
+    // Insérer un résultat de détection ; les objets déjà vus récemment incrémentent leur ref_count
+    pub async fn insert_detection(
         &self,
+        request_id: &str,
         g_id: &str,
-        limit: Option<i64>,
-    ) -> Result<Vec<Value>, sqlx::Error> {
-        let limit = limit.unwrap_or(50);
+        objects: &[DetectedObject],
+    ) -> Result<InsertOutcome, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut outcome = InsertOutcome::default();
 
-        let query = "SELECT g_id, request_id, detected_objects, confidence_scores, timestamp FROM detections WHERE g_id = ? ORDER BY timestamp DESC LIMIT ?";
-        let rows = sqlx::query(query)
-            .bind(g_id)
-            .bind(limit)
-            .fetch_all(&self.pool)
+        let mut new_objects = Vec::new();
+        for object in objects {
+            let sighting = if self.dedup.window_secs > 0 {
+                self.find_recent_sighting(&mut tx, request_id, g_id, object).await?
+            } else {
+                None
+            };
+
+            let Some((object_id, detection_id)) = sighting else {
+                new_objects.push(object);
+                continue;
+            };
+
+            let bbox = object.bbox.as_ref();
+            sqlx::query(
+                "UPDATE detected_objects
+                 SET ref_count = ref_count + 1,
+                     last_seen = CURRENT_TIMESTAMP,
+                     confidence = MAX(confidence, ?),
+                     bbox_x = COALESCE(?, bbox_x),
+                     bbox_y = COALESCE(?, bbox_y),
+                     bbox_width = COALESCE(?, bbox_width),
+                     bbox_height = COALESCE(?, bbox_height),
+                     track_id = COALESCE(?, track_id)
+                 WHERE id = ?"
+            )
+            .bind(object.confidence)
+            .bind(bbox.map(|bbox| bbox.x))
+            .bind(bbox.map(|bbox| bbox.y))
+            .bind(bbox.map(|bbox| bbox.width))
+            .bind(bbox.map(|bbox| bbox.height))
+            .bind(object.track_id)
+            .bind(object_id)
+            .execute(&mut *tx)
             .await?;
 
-        let mut detections = Vec::new();
-        for row in rows {
-            let detection = serde_json::json!({
-                "g_id": row.get::<String, _>("g_id"),
-                "request_id": row.get::<String, _>("request_id"),
-                "detected_objects": row.get::<String, _>("detected_objects"),
-                "confidence_scores": row.get::<String, _>("confidence_scores"),
-                "timestamp": row.get::<String, _>("timestamp"),
-            });
-            detections.push(detection);
+            if !outcome.merged_into.contains(&detection_id) {
+                outcome.merged_into.push(detection_id);
+            }
+        }
+
+        if !new_objects.is_empty() {
+            let id = sqlx::query("INSERT INTO detections (request_id, g_id) VALUES (?, ?)")
+                .bind(request_id)
+                .bind(g_id)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid();
+            outcome.detection_id = Some(id);
+        }
+
+        for object in new_objects {
+            let bbox = object.bbox.as_ref();
+            sqlx::query(
+                "INSERT INTO detected_objects
+                    (request_id, class, color, confidence, bbox_x, bbox_y, bbox_width, bbox_height, track_id, first_seen, last_seen)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)"
+            )
+            .bind(request_id)
+            .bind(&object.class)
+            .bind(&object.color)
+            .bind(object.confidence)
+            .bind(bbox.map(|bbox| bbox.x))
+            .bind(bbox.map(|bbox| bbox.y))
+            .bind(bbox.map(|bbox| bbox.width))
+            .bind(bbox.map(|bbox| bbox.height))
+            .bind(object.track_id)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(outcome)
+    }
+
+    // Charger les objets détectés des résultats donnés
+    async fn attach_objects(&self, records: &mut [DetectionRecord]) -> Result<(), sqlx::Error> {
+        if records.is_empty() {
+            return Ok(());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT request_id, class, color, confidence, bbox_x, bbox_y, bbox_width, bbox_height, ref_count, track_id
+             FROM detected_objects WHERE request_id IN ("
+        );
+        let mut separated = query.separated(", ");
+        for record in records.iter() {
+            separated.push_bind(&record.request_id);
+        }
+        query.push(") ORDER BY confidence DESC, id");
+
+        let mut objects: HashMap<String, Vec<DetectedObject>> = HashMap::new();
+        for row in query.build().fetch_all(&self.pool).await? {
+            objects
+                .entry(row.get("request_id"))
+                .or_default()
+                .push(object_from_row(&row, ""));
+        }
+
+        for record in records.iter_mut() {
+            record.objects = objects.remove(&record.request_id).unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    // Récupérer un résultat de détection par son identifiant
+    pub async fn get_detection(&self, id: i64) -> Result<Option<DetectionRecord>, sqlx::Error> {
+        let row = sqlx::query(&format!("{} WHERE d.id = ?", RECORD_SELECT))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let mut records: Vec<DetectionRecord> = row.iter().map(record_from_row).collect();
+        self.attach_objects(&mut records).await?;
+        Ok(records.pop())
+    }
+
+    // Enregistrer une détection envoyée par le dashboard ou detection.py
+    pub async fn insert_manual_detection(
+        &self,
+        g_id: &str,
+        object_type: &str,
+        color: &str,
+        device_id: Option<i64>,
+    ) -> Result<DetectionRecord, sqlx::Error> {
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let object = DetectedObject {
+            class: object_type.to_string(),
+            color: Some(color.to_string()),
+            confidence: 1.0,
+            bbox: None,
+            ref_count: 1,
+            track_id: None,
+        };
+
+        // Les détections manuelles n'ont pas d'image associée
+        self.insert_detection_request(g_id, &request_id, "", device_id).await?;
+        self.update_request_status(&request_id, "done").await?;
+        let outcome = self.insert_detection(&request_id, g_id, &[object]).await?;
+
+        // Objet déjà vu : renvoyer la détection existante avec son ref_count à jour
+        let id = outcome
+            .detection_id
+            .or(outcome.merged_into.first().copied())
+            .ok_or(sqlx::Error::RowNotFound)?;
+        self.get_detection(id)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
+    }
+
+    // Rechercher dans l'historique avec filtres, tri et pagination par curseur
+    pub async fn search_detections(
+        &self,
+        query: &DetectionQuery,
+        cursor: Option<&PageCursor>,
+    ) -> Result<DetectionPage, sqlx::Error> {
+        let mut count = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) as count FROM detections d WHERE 1 = 1");
+        push_filters(&mut count, query);
+        let total = count.build().fetch_one(&self.pool).await?.get("count");
+
+        let (direction, comparison) = sort_direction(query.order);
+        let key = match query.sort {
+            SortField::Datetime => "d.timestamp",
+            SortField::Confidence => "COALESCE(o.confidence, 0)",
+        };
+
+        let mut select = QueryBuilder::<Sqlite>::new(RECORD_SELECT);
+        select.push(" WHERE 1 = 1");
+        push_filters(&mut select, query);
+        if let Some(cursor) = cursor {
+            select.push(format!(" AND ({key}, d.id) {comparison} ("));
+            match query.sort {
+                SortField::Datetime => select.push_bind(cursor.value.clone()),
+                SortField::Confidence => select.push_bind(cursor.value.parse::<f32>().unwrap_or_default()),
+            };
+            select.push(", ").push_bind(cursor.id).push(")");
         }
 
-        Ok(detections)
+        // Une ligne de plus pour savoir s'il reste une page suivante
+        let page_size = query.page_size();
+        select
+            .push(format!(" ORDER BY {key} {direction}, d.id {direction} LIMIT "))
+            .push_bind(page_size + 1);
+
+        let rows = select.build().fetch_all(&self.pool).await?;
+        let mut items: Vec<DetectionRecord> = rows.iter().map(record_from_row).collect();
+        let has_more = items.len() as i64 > page_size;
+        items.truncate(page_size as usize);
+        self.attach_objects(&mut items).await?;
+
+        let next_cursor = items
+            .last()
+            .filter(|_| has_more)
+            .map(|last| PageCursor::after(last, query.sort).encode());
+
+        Ok(DetectionPage {
+            items,
+            total,
+            next_cursor,
+        })
+    }
+
+    // Parcourir toutes les détections filtrées sans les charger en mémoire (curseur et limite ignorés)
+    pub fn stream_detections(&self, query: DetectionQuery) -> mpsc::Receiver<Result<DetectionRecord, sqlx::Error>> {
+        let (tx, rx) = mpsc::channel(EXPORT_BUFFER);
+        let pool = self.pool.clone();
+
+        tokio::spawn(async move {
+            let (direction, _) = sort_direction(query.order);
+            let key = match query.sort {
+                SortField::Datetime => "rec.timestamp",
+                SortField::Confidence => "rec.confidence",
+            };
+
+            // Une ligne par objet : les lignes consécutives d'un même id forment un DetectionRecord
+            let mut select = QueryBuilder::<Sqlite>::new(format!(
+                "SELECT rec.*, ob.class as object_class, ob.color as object_color, ob.confidence as object_confidence,
+                        ob.bbox_x as object_bbox_x, ob.bbox_y as object_bbox_y,
+                        ob.bbox_width as object_bbox_width, ob.bbox_height as object_bbox_height,
+                        ob.ref_count as object_ref_count, ob.track_id as object_track_id
+                 FROM ({} WHERE 1 = 1",
+                RECORD_SELECT
+            ));
+            push_filters(&mut select, &query);
+            select.push(format!(
+                ") rec LEFT JOIN detected_objects ob ON ob.request_id = rec.request_id
+                 ORDER BY {key} {direction}, rec.id {direction}, ob.confidence DESC, ob.id"
+            ));
+
+            let mut rows = select.build().fetch(&pool);
+            let mut current: Option<DetectionRecord> = None;
+            loop {
+                let row = match rows.try_next().await {
+                    Ok(Some(row)) => row,
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = tx.send(Err(e)).await;
+                        return;
+                    }
+                };
+
+                let id: i64 = row.get("id");
+                if let Some(record) = current.take_if(|record| record.id != id) {
+                    // Le client s'est déconnecté
+                    if tx.send(Ok(record)).await.is_err() {
+                        return;
+                    }
+                }
+
+                let record = current.get_or_insert_with(|| record_from_row(&row));
+                if row.get::<Option<String>, _>("object_class").is_some() {
+                    record.objects.push(object_from_row(&row, "object_"));
+                }
+            }
+
+            if let Some(record) = current {
+                let _ = tx.send(Ok(record)).await;
+            }
+        });
+
+        rx
+    }
+
+    // Cadence par jour et par type sur les détections filtrées
+    pub async fn daily_cadence(&self, query: &DetectionQuery) -> Result<Vec<DailyCadence>, sqlx::Error> {
+        let mut select = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT DATE(rec.timestamp) as day, rec.type, COUNT(*) as count FROM ({} WHERE 1 = 1",
+            RECORD_SELECT
+        ));
+        push_filters(&mut select, query);
+        select.push(") rec GROUP BY day, rec.type ORDER BY day, rec.type");
+
+        let rows = select.build().fetch_all(&self.pool).await?;
+        Ok(rows
+            .iter()
+            .map(|row| DailyCadence {
+                day: row.get("day"),
+                object_type: row.get("type"),
+                count: row.get("count"),
+            })
+            .collect())
+    }
+
+    // Dernières détections, éventuellement pour un seul g_id
+    pub async fn list_detections(
+        &self,
+        g_id: Option<&str>,
+        limit: Option<i64>,
+    ) -> Result<Vec<DetectionRecord>, sqlx::Error> {
+        let query = DetectionQuery {
+            g_id: g_id.map(str::to_string),
+            limit,
+            ..Default::default()
+        };
+
+        Ok(self.search_detections(&query, None).await?.items)
     }
 
-    // Récupérer les statistiques de détection
-    pub async fn get_detection_stats(&self, g_id: &str) -> Result<Value, sqlx::Error> {
-        // Statistiques d'aujourd'hui
+    // Récupérer les statistiques de détection (toutes sources ou un seul g_id)
+    pub async fn get_stats(&self, g_id: Option<&str>) -> Result<DetectionStats, sqlx::Error> {
         let today_stats = sqlx::query(
-            "SELECT COUNT(*) as count FROM detections WHERE g_id = ? AND DATE(timestamp) = DATE('now')"
+            "SELECT COUNT(*) as count FROM detections WHERE (?1 IS NULL OR g_id = ?1) AND DATE(timestamp) = DATE('now')"
         )
         .bind(g_id)
         .fetch_one(&self.pool)
         .await?;
 
-        // Statistiques totales
-        let total_stats = sqlx::query(
-            "SELECT COUNT(*) as count FROM detections WHERE g_id = ?"
-        )
-        .bind(g_id)
-        .fetch_one(&self.pool)
-        .await?;
+        let total_stats = sqlx::query("SELECT COUNT(*) as count FROM detections WHERE (?1 IS NULL OR g_id = ?1)")
+            .bind(g_id)
+            .fetch_one(&self.pool)
+            .await?;
 
-        // Détections récentes
-        let recent_detections = sqlx::query(
-            "SELECT request_id, detected_objects, confidence_scores, timestamp 
-             FROM detections 
-             WHERE g_id = ? 
-             ORDER BY timestamp DESC 
-             LIMIT 5"
-        )
-        .bind(g_id)
+        Ok(DetectionStats {
+            today_count: today_stats.get("count"),
+            total_count: total_stats.get("count"),
+            recent_detections: self.list_detections(g_id, Some(5)).await?,
+        })
+    }
+
+    // Cadence journalière lue dans daily_stats (tenue à jour par triggers)
+    pub async fn get_daily_stats(
+        &self,
+        query: &DailyStatsQuery,
+        group_columns: &[&str],
+    ) -> Result<Vec<DailyStat>, sqlx::Error> {
+        let mut columns = vec!["day"];
+        columns.extend_from_slice(group_columns);
+        let columns = columns.join(", ");
+
+        let mut select = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {columns}, SUM(cadence) as cadence FROM daily_stats WHERE 1 = 1"
+        ));
+        if let Some(from) = query.from {
+            select.push(" AND day >= ").push_bind(from.to_string());
+        }
+        if let Some(to) = query.to {
+            select.push(" AND day <= ").push_bind(to.to_string());
+        }
+        if let Some(g_id) = &query.g_id {
+            select.push(" AND g_id = ").push_bind(g_id.clone());
+        }
+        select
+            .push(" AND rule = ")
+            .push_bind(query.rule.clone().unwrap_or_default());
+        select.push(format!(" GROUP BY {columns} ORDER BY {columns}"));
+
+        let grouped = |column: &str| group_columns.contains(&column);
+        let rows = select.build().fetch_all(&self.pool).await?;
+        Ok(rows
+            .iter()
+            .map(|row| DailyStat {
+                day: row.get("day"),
+                g_id: grouped("g_id").then(|| row.get("g_id")),
+                object_type: grouped("type").then(|| row.get("type")),
+                color: grouped("color").then(|| row.get("color")),
+                event: grouped("event").then(|| row.get("event")),
+                cadence: row.get("cadence"),
+            })
+            .collect())
+    }
+
+    // Débit par intervalle et par type ; les intervalles vides sont complétés par des zéros
+    pub async fn get_throughput(
+        &self,
+        query: &ThroughputQuery,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Result<Throughput, sqlx::Error> {
+        let from = from.format("%Y-%m-%d %H:%M:%S").to_string();
+        let to = to.format("%Y-%m-%d %H:%M:%S").to_string();
+
+        // ?1 format, ?2 début, ?3 pas, ?4 fin
+        const BUCKETS: &str = "
+            WITH RECURSIVE buckets(bucket) AS (
+                SELECT strftime(?1, ?2)
+                UNION ALL
+                SELECT strftime(?1, bucket, ?3) FROM buckets WHERE bucket < strftime(?1, ?4)
+            )";
+
+        let buckets: Vec<String> = sqlx::query(&format!("{} SELECT bucket FROM buckets", BUCKETS))
+            .bind(query.interval.bucket_format())
+            .bind(&from)
+            .bind(query.interval.step())
+            .bind(&to)
+            .fetch_all(&self.pool)
+            .await?
+            .iter()
+            .map(|row| row.get("bucket"))
+            .collect();
+
+        // Grille intervalles × types, 0 pour les intervalles sans détection
+        let rows = sqlx::query(&format!(
+            "{},
+            counts AS (
+                SELECT strftime(?1, d.timestamp) as bucket, o.class as type, COUNT(*) as count
+                FROM detected_objects o
+                JOIN detections d ON d.request_id = o.request_id
+                WHERE d.timestamp >= strftime(?1, ?2) AND strftime(?1, d.timestamp) <= strftime(?1, ?4)
+                  AND (?5 IS NULL OR d.g_id = ?5)
+                  AND (?6 IS NULL OR o.class = ?6)
+                GROUP BY 1, 2
+            ),
+            types AS (SELECT DISTINCT type FROM counts)
+            SELECT t.type, COALESCE(c.count, 0) as count
+            FROM types t
+            CROSS JOIN buckets b
+            LEFT JOIN counts c ON c.bucket = b.bucket AND c.type = t.type
+            ORDER BY t.type, b.bucket",
+            BUCKETS
+        ))
+        .bind(query.interval.bucket_format())
+        .bind(&from)
+        .bind(query.interval.step())
+        .bind(&to)
+        .bind(&query.g_id)
+        .bind(&query.object_type)
         .fetch_all(&self.pool)
         .await?;
 
-        let mut recent_vec = Vec::new();
-        for row in recent_detections {
-            let detection = serde_json::json!({
-                "request_id": row.get::<String, _>("request_id"),
-                "detected_objects": row.get::<String, _>("detected_objects"),
-                "confidence_scores": row.get::<String, _>("confidence_scores"),
-                "timestamp": row.get::<String, _>("timestamp"),
-            });
-            recent_vec.push(detection);
+        let mut series: Vec<ThroughputSeries> = Vec::new();
+        for row in rows {
+            let object_type: String = row.get("type");
+            let count: i64 = row.get("count");
+            match series.last_mut() {
+                Some(last) if last.object_type == object_type => {
+                    last.counts.push(count);
+                    last.total += count;
+                }
+                _ => series.push(ThroughputSeries {
+                    object_type,
+                    counts: vec![count],
+                    total: count,
+                }),
+            }
         }
 
-        let stats = serde_json::json!({
-            "today_count": today_stats.get::<i64, _>("count"),
-            "total_count": total_stats.get::<i64, _>("count"),
-            "recent_detections": recent_vec
-        });
-
-        Ok(stats)
+        Ok(Throughput {
+            interval: query.interval,
+            buckets,
+            series,
+        })
     }
 
-    // Supprimer les anciennes détections (plus de 30 jours)
-    #[allow(dead_code)]
-    pub async fn cleanup_old_detections(&self) -> Result<(), sqlx::Error> {
-        // Supprimer les détections de plus de 30 jours
-        let deleted = sqlx::query("DELETE FROM detections WHERE timestamp < DATE('now', '-30 days')")
-            .execute(&self.pool)
-            .await?;
+    // Supprimer une détection et ses objets
+    pub async fn delete_detection(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
 
-        // Supprimer les requêtes de détection correspondantes
-        sqlx::query("DELETE FROM detection_requests WHERE timestamp < DATE('now', '-30 days')")
-            .execute(&self.pool)
+        sqlx::query(
+            "DELETE FROM detected_objects WHERE request_id = (SELECT request_id FROM detections WHERE id = ?)"
+        )
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+
+        let deleted = sqlx::query("DELETE FROM detections WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
             .await?;
 
-        tracing::info!("Supprimé {} anciennes détections", deleted.rows_affected());
-        Ok(())
+        tx.commit().await?;
+        Ok(deleted.rows_affected() > 0)
     }
-}
\ No newline at end of file
+
+    // Règles de comptage, éventuellement d'une seule caméra
+    pub async fn list_counting_rules(&self, g_id: Option<&str>) -> Result<Vec<CountingRule>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT id, g_id, name, kind, points, direction, created_at FROM counting_rules
+             WHERE (?1 IS NULL OR g_id = ?1) ORDER BY g_id, id"
+        )
+        .bind(g_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.iter().filter_map(rule_from_row).collect())
+    }
+
+    pub async fn insert_counting_rule(&self, rule: &NewCountingRule) -> Result<CountingRule, sqlx::Error> {
+        let direction = match rule.kind {
+            RuleKind::Line => rule.direction,
+            RuleKind::Zone => LineDirection::Both,
+        };
+        let row = sqlx::query(
+            "INSERT INTO counting_rules (g_id, name, kind, points, direction) VALUES (?, ?, ?, ?, ?)
+             RETURNING id, g_id, name, kind, points, direction, created_at"
+        )
+        .bind(rule.g_id.trim())
+        .bind(rule.name.trim())
+        .bind(rule.kind.as_str())
+        .bind(serde_json::to_string(&rule.points).unwrap_or_default())
+        .bind(direction.as_str())
+        .fetch_one(&self.pool)
+        .await?;
+
+        rule_from_row(&row).ok_or(sqlx::Error::RowNotFound)
+    }
+
+    // Les événements déjà comptés sont conservés (rule_id passe à NULL)
+    pub async fn delete_counting_rule(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM counting_rules WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    // Enregistrer les événements de comptage ; daily_stats suit par trigger
+    pub async fn insert_counting_events(&self, g_id: &str, events: &[CountingEvent]) -> Result<(), sqlx::Error> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = QueryBuilder::<Sqlite>::new(
+            "INSERT INTO counting_events (rule_id, rule, g_id, track_id, class, color, event) "
+        );
+        insert.push_values(events, |mut values, event| {
+            values
+                .push_bind(event.rule_id)
+                .push_bind(event.rule.clone())
+                .push_bind(g_id.to_string())
+                .push_bind(event.track_id as i64)
+                .push_bind(event.class.clone())
+                .push_bind(event.color.clone())
+                .push_bind(event.event.as_str());
+        });
+        insert.build().execute(&self.pool).await?;
+
+        Ok(())
+    }
+
+    // Tous les appareils, ou seulement ceux d'un statut donné
+    pub async fn list_devices(&self, status: Option<DeviceStatus>) -> Result<Vec<Device>, sqlx::Error> {
+        let mut builder = QueryBuilder::<Sqlite>::new(format!("SELECT {} FROM devices", DEVICE_COLUMNS));
+        if let Some(status) = status {
+            builder.push(" WHERE status = ").push_bind(status.as_str());
+        }
+        builder.push(" ORDER BY id");
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+
+        Ok(rows.iter().map(device_from_row).collect())
+    }
+
+    pub async fn get_device(&self, id: i64) -> Result<Option<Device>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM devices WHERE id = ?", DEVICE_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(device_from_row))
+    }
+
+    // Appareil correspondant au hash d'une clé d'API
+    pub async fn find_device_by_key(&self, key_hash: &str) -> Result<Option<Device>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM devices WHERE api_key_hash = ?", DEVICE_COLUMNS))
+            .bind(key_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(device_from_row))
+    }
+
+    // Créer un appareil avec le hash de sa clé d'API
+    pub async fn insert_device(&self, device: &NewDevice, api_key: &str) -> Result<Device, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO devices (name, location, type, enabled, api_key_hash, key_prefix) VALUES (?, ?, ?, ?, ?, ?)
+             RETURNING {}",
+            DEVICE_COLUMNS
+        ))
+        .bind(device.name.trim())
+        .bind(&device.location)
+        .bind(&device.device_type)
+        .bind(device.enabled)
+        .bind(devices::hash_api_key(api_key))
+        .bind(devices::key_prefix(api_key))
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(device_from_row(&row))
+    }
+
+    pub async fn update_device(&self, id: i64, update: &DeviceUpdate) -> Result<Option<Device>, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "UPDATE devices SET
+                name = COALESCE(?, name),
+                location = COALESCE(?, location),
+                type = COALESCE(?, type),
+                enabled = COALESCE(?, enabled)
+             WHERE id = ?
+             RETURNING {}",
+            DEVICE_COLUMNS
+        ))
+        .bind(update.name.as_deref().map(str::trim))
+        .bind(&update.location)
+        .bind(&update.device_type)
+        .bind(update.enabled)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(device_from_row))
+    }
+
+    // Remplacer la clé d'API ; l'ancienne cesse immédiatement de fonctionner
+    pub async fn rotate_device_key(&self, id: i64, api_key: &str) -> Result<Option<Device>, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "UPDATE devices SET api_key_hash = ?, key_prefix = ? WHERE id = ? RETURNING {}",
+            DEVICE_COLUMNS
+        ))
+        .bind(devices::hash_api_key(api_key))
+        .bind(devices::key_prefix(api_key))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(device_from_row))
+    }
+
+    // Tracer un changement de statut dans device_status_history
+    async fn push_status_change(
+        tx: &mut sqlx::SqliteConnection,
+        device_id: i64,
+        status: DeviceStatus,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("INSERT INTO device_status_history (device_id, status) VALUES (?, ?)")
+            .bind(device_id)
+            .bind(status.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        Ok(())
+    }
+
+    // Battement de cœur : last_seen_at à maintenant, l'appareil repasse en ligne si besoin
+    pub async fn record_heartbeat(&self, device: &Device) -> Result<Device, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let row = sqlx::query(&format!(
+            "UPDATE devices SET last_seen_at = CURRENT_TIMESTAMP, status = 'online' WHERE id = ? RETURNING {}",
+            DEVICE_COLUMNS
+        ))
+        .bind(device.id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if device.status != DeviceStatus::Online {
+            Self::push_status_change(&mut tx, device.id, DeviceStatus::Online).await?;
+            println!("🟢 Appareil en ligne: {}", device.name);
+        }
+
+        tx.commit().await?;
+        Ok(device_from_row(&row))
+    }
+
+    // Passer hors ligne les appareils sans battement de cœur depuis timeout_secs
+    pub async fn mark_offline_devices(&self, timeout_secs: u64) -> Result<Vec<Device>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query(&format!(
+            "UPDATE devices SET status = 'offline'
+             WHERE status = 'online' AND last_seen_at < datetime('now', ?)
+             RETURNING {}",
+            DEVICE_COLUMNS
+        ))
+        .bind(format!("-{} seconds", timeout_secs))
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let devices: Vec<Device> = rows.iter().map(device_from_row).collect();
+        for device in &devices {
+            Self::push_status_change(&mut tx, device.id, DeviceStatus::Offline).await?;
+        }
+
+        tx.commit().await?;
+        Ok(devices)
+    }
+
+    // Derniers changements de statut d'un appareil, du plus récent au plus ancien
+    pub async fn device_status_history(&self, id: i64, limit: i64) -> Result<Vec<DeviceStatusChange>, sqlx::Error> {
+        let rows = sqlx::query(
+            "SELECT status, changed_at FROM device_status_history
+             WHERE device_id = ? ORDER BY changed_at DESC, id DESC LIMIT ?"
+        )
+        .bind(id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| DeviceStatusChange {
+                status: DeviceStatus::parse(row.get("status")),
+                changed_at: row.get("changed_at"),
+            })
+            .collect())
+    }
+
+    // Statut des appareils activés pour /health
+    pub async fn devices_health(&self) -> Result<DevicesHealth, sqlx::Error> {
+        let rows = sqlx::query("SELECT status FROM devices WHERE enabled")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut health = DevicesHealth::default();
+        for row in &rows {
+            match DeviceStatus::parse(row.get("status")) {
+                DeviceStatus::Online => health.online += 1,
+                DeviceStatus::Unknown => health.unknown += 1,
+                DeviceStatus::Offline => health.offline += 1,
+            }
+        }
+        Ok(health)
+    }
+
+    // Les détections de l'appareil sont conservées, sans attribution
+    pub async fn delete_device(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM devices WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        let rows = sqlx::query(&format!("SELECT {} FROM users ORDER BY id", USER_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.iter().map(user_from_row).collect())
+    }
+
+    pub async fn get_user(&self, id: i64) -> Result<Option<User>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE id = ?", USER_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(user_from_row))
+    }
+
+    // Compte et hash de son mot de passe, pour la connexion
+    pub async fn find_user_credentials(&self, username: &str) -> Result<Option<(User, String)>, sqlx::Error> {
+        let row = sqlx::query(&format!("SELECT {}, password_hash FROM users WHERE username = ?", USER_COLUMNS))
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| (user_from_row(&row), row.get("password_hash"))))
+    }
+
+    pub async fn insert_user(&self, user: &NewUser, password_hash: &str) -> Result<User, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO users (username, password_hash, role, enabled) VALUES (?, ?, ?, ?) RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(user.username.trim())
+        .bind(password_hash)
+        .bind(&user.role)
+        .bind(user.enabled)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(user_from_row(&row))
+    }
+
+    // Au moins un compte existe-t-il ?
+    pub async fn has_users(&self) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query("SELECT 1 FROM users LIMIT 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.is_some())
+    }
+
+    // Créer l'administrateur initial, uniquement si aucun compte n'existe
+    pub async fn create_first_admin(&self, username: &str, password_hash: &str) -> Result<bool, sqlx::Error> {
+        let inserted = sqlx::query(
+            "INSERT INTO users (username, password_hash, role)
+             SELECT ?, ?, ? WHERE NOT EXISTS (SELECT 1 FROM users)"
+        )
+        .bind(username)
+        .bind(password_hash)
+        .bind(Role::Admin.as_str())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(inserted.rows_affected() > 0)
+    }
+
+    pub async fn update_user(
+        &self,
+        id: i64,
+        update: &UserUpdate,
+        password_hash: Option<&str>,
+    ) -> Result<Option<User>, sqlx::Error> {
+        let row = sqlx::query(&format!(
+            "UPDATE users SET
+                role = COALESCE(?, role),
+                enabled = COALESCE(?, enabled),
+                password_hash = COALESCE(?, password_hash),
+                updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?
+             RETURNING {}",
+            USER_COLUMNS
+        ))
+        .bind(&update.role)
+        .bind(update.enabled)
+        .bind(password_hash)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.as_ref().map(user_from_row))
+    }
+
+    pub async fn set_user_password(&self, id: i64, password_hash: &str) -> Result<bool, sqlx::Error> {
+        let updated = sqlx::query("UPDATE users SET password_hash = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(password_hash)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(updated.rows_affected() > 0)
+    }
+
+    // Administrateurs actifs, pour ne jamais supprimer ou rétrograder le dernier
+    pub async fn count_active_admins(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE role = ? AND enabled")
+            .bind(Role::Admin.as_str())
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn delete_user(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let deleted = sqlx::query("DELETE FROM users WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(deleted.rows_affected() > 0)
+    }
+
+    // Ouvrir une session avec son premier jeton de rafraîchissement (les sessions expirées sont purgées)
+    pub async fn create_session(
+        &self,
+        session_id: &str,
+        user_id: i64,
+        user_agent: Option<&str>,
+        refresh_hash: &str,
+        refresh_ttl: chrono::Duration,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM sessions WHERE expires_at < datetime('now')")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("INSERT INTO sessions (id, user_id, user_agent, expires_at) VALUES (?, ?, ?, datetime('now', ?))")
+            .bind(session_id)
+            .bind(user_id)
+            .bind(user_agent)
+            .bind(from_now(refresh_ttl))
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("INSERT INTO refresh_tokens (session_id, token_hash, expires_at) VALUES (?, ?, datetime('now', ?))")
+            .bind(session_id)
+            .bind(refresh_hash)
+            .bind(from_now(refresh_ttl))
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await
+    }
+
+    // Échanger un jeton de rafraîchissement contre un nouveau. Un jeton déjà échangé
+    // révoque toute la session : l'original et sa copie cessent de fonctionner.
+    pub async fn rotate_refresh_token(
+        &self,
+        refresh_hash: &str,
+        new_refresh_hash: &str,
+        refresh_ttl: chrono::Duration,
+    ) -> Result<RefreshOutcome, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        // Le jeton est réclamé par une écriture conditionnelle, première instruction de la
+        // transaction : deux échanges simultanés sont sérialisés et un seul peut le consommer
+        let claimed = sqlx::query(
+            "UPDATE refresh_tokens SET used_at = CURRENT_TIMESTAMP
+             WHERE token_hash = ? AND used_at IS NULL
+             RETURNING session_id, expires_at < datetime('now') AS expired"
+        )
+        .bind(refresh_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(claimed) = claimed else {
+            let used = sqlx::query_scalar::<_, String>("SELECT session_id FROM refresh_tokens WHERE token_hash = ?")
+                .bind(refresh_hash)
+                .fetch_optional(&mut *tx)
+                .await?;
+            let Some(session_id) = used else {
+                return Ok(RefreshOutcome::Invalid);
+            };
+
+            sqlx::query("UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP, revoked_reason = ? WHERE id = ? AND revoked_at IS NULL")
+                .bind(RevokeReason::Reuse.as_str())
+                .bind(&session_id)
+                .execute(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            return Ok(RefreshOutcome::Reused { session_id });
+        };
+        let session_id: String = claimed.get("session_id");
+
+        // Jeton expiré, session révoquée ou compte désactivé : abandon (la transaction est annulée)
+        let session = sqlx::query("SELECT user_id, revoked_at IS NOT NULL AS revoked FROM sessions WHERE id = ?")
+            .bind(&session_id)
+            .fetch_one(&mut *tx)
+            .await?;
+        let user = sqlx::query(&format!("SELECT {} FROM users WHERE id = ?", USER_COLUMNS))
+            .bind(session.get::<i64, _>("user_id"))
+            .fetch_one(&mut *tx)
+            .await?;
+        let user = user_from_row(&user);
+        if claimed.get::<bool, _>("expired") || session.get::<bool, _>("revoked") || !user.enabled {
+            return Ok(RefreshOutcome::Invalid);
+        }
+
+        sqlx::query("INSERT INTO refresh_tokens (session_id, token_hash, expires_at) VALUES (?, ?, datetime('now', ?))")
+            .bind(&session_id)
+            .bind(new_refresh_hash)
+            .bind(from_now(refresh_ttl))
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("UPDATE sessions SET last_used_at = CURRENT_TIMESTAMP, expires_at = datetime('now', ?) WHERE id = ?")
+            .bind(from_now(refresh_ttl))
+            .bind(&session_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(RefreshOutcome::Rotated { session_id, user })
+    }
+
+    // Les jetons d'accès d'une session révoquée ou expirée sont refusés
+    pub async fn session_is_active(&self, session_id: &str) -> Result<bool, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT 1 FROM sessions WHERE id = ? AND revoked_at IS NULL AND expires_at > datetime('now')"
+        )
+        .bind(session_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.is_some())
+    }
+
+    pub async fn get_session(&self, session_id: &str) -> Result<Option<Session>, sqlx::Error> {
+        let row = sqlx::query(&format!("{} WHERE s.id = ?", SESSION_SELECT))
+            .bind(session_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.as_ref().map(session_from_row))
+    }
+
+    // Sessions actives d'un utilisateur, ou de tous si user_id est absent
+    pub async fn list_sessions(&self, user_id: Option<i64>) -> Result<Vec<Session>, sqlx::Error> {
+        let mut builder = QueryBuilder::<Sqlite>::new(SESSION_SELECT);
+        builder.push(" WHERE s.revoked_at IS NULL AND s.expires_at > datetime('now')");
+        if let Some(user_id) = user_id {
+            builder.push(" AND s.user_id = ").push_bind(user_id);
+        }
+        builder.push(" ORDER BY s.last_used_at DESC");
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(session_from_row).collect())
+    }
+
+    // Révoquer une session (limitée aux sessions de user_id s'il est fourni)
+    pub async fn revoke_session(
+        &self,
+        session_id: &str,
+        user_id: Option<i64>,
+        reason: RevokeReason,
+    ) -> Result<bool, sqlx::Error> {
+        let revoked = sqlx::query(
+            "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP, revoked_reason = ?
+             WHERE id = ? AND revoked_at IS NULL AND (? IS NULL OR user_id = ?)"
+        )
+        .bind(reason.as_str())
+        .bind(session_id)
+        .bind(user_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(revoked.rows_affected() > 0)
+    }
+
+    // Révoquer toutes les sessions d'un utilisateur, sauf éventuellement la session courante
+    pub async fn revoke_user_sessions(
+        &self,
+        user_id: i64,
+        except: Option<&str>,
+        reason: RevokeReason,
+    ) -> Result<u64, sqlx::Error> {
+        let revoked = sqlx::query(
+            "UPDATE sessions SET revoked_at = CURRENT_TIMESTAMP, revoked_reason = ?
+             WHERE user_id = ? AND revoked_at IS NULL AND (? IS NULL OR id != ?)"
+        )
+        .bind(reason.as_str())
+        .bind(user_id)
+        .bind(except)
+        .bind(except)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(revoked.rows_affected())
+    }
+
+    // Vider toutes les tables de détection (les règles de comptage et les appareils sont conservés)
+    pub async fn reset(&self) -> Result<(), sqlx::Error> {
+        // Tout ou rien : une erreur ne doit pas laisser une base à moitié vidée
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM counting_events")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM detected_objects")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM daily_stats")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM detections")
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM detection_requests")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        println!("🗑️ Base de données réinitialisée");
+        Ok(())
+    }
+
+    // Supprimer les anciennes détections (plus de 30 jours)
+    #[allow(dead_code)]
+    pub async fn cleanup_old_detections(&self) -> Result<(), sqlx::Error> {
+        // Supprimer les détections de plus de 30 jours
+        let deleted = sqlx::query("DELETE FROM detections WHERE timestamp < DATE('now', '-30 days')")
+            .execute(&self.pool)
+            .await?;
+
+        // Supprimer les requêtes correspondantes (et leurs objets, par cascade)
+        sqlx::query("DELETE FROM detection_requests WHERE timestamp < DATE('now', '-30 days')")
+            .execute(&self.pool)
+            .await?;
+
+        println!("🧹 {} anciennes détections supprimées", deleted.rows_affected());
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn memory_database() -> Database {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        migrations::run(&pool).await.unwrap();
+        // Fusion désactivée : chaque insertion reste une détection distincte
+        Database::new(pool).with_dedup(DedupConfig {
+            window_secs: 0,
+            ..Default::default()
+        })
+    }
+
+    fn object(class: &str, confidence: f32) -> DetectedObject {
+        DetectedObject {
+            class: class.to_string(),
+            color: None,
+            confidence,
+            bbox: Some(BoundingBox {
+                x: 1.0,
+                y: 2.0,
+                width: 3.0,
+                height: 4.0,
+            }),
+            ref_count: 1,
+            track_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_detection_round_trip() {
+        let db = memory_database().await;
+        db.insert_detection_request("CAM_1", "r1", "", None).await.unwrap();
+        db.insert_detection("r1", "CAM_1", &[object("STM32", 0.6), object("Carte microchip", 0.9)])
+            .await
+            .unwrap();
+        db.insert_manual_detection("CAM_2", "STM32", "blue", None).await.unwrap();
+
+        let records = db.list_detections(Some("CAM_1"), None).await.unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].object_type, "Carte microchip");
+        assert_eq!(records[0].objects.len(), 2);
+        assert_eq!(records[0].objects[1].bbox.as_ref().unwrap().height, 4.0);
+
+        let stats = db.get_stats(None).await.unwrap();
+        assert_eq!(stats.total_count, 2);
+        assert!(stats.recent_detections[0].objects[0].bbox.is_none());
+
+        assert!(db.delete_detection(records[0].id).await.unwrap());
+        assert_eq!(db.get_stats(Some("CAM_1")).await.unwrap().total_count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_search_filters_and_paginates() {
+        let db = memory_database().await;
+        for (i, confidence) in [0.3, 0.9, 0.5, 0.7, 0.8].into_iter().enumerate() {
+            let request_id = format!("r{}", i);
+            db.insert_detection_request("CAM_1", &request_id, "", None).await.unwrap();
+            let class = if i % 2 == 0 { "STM32" } else { "Carte microchip" };
+            db.insert_detection(&request_id, "CAM_1", &[object(class, confidence)])
+                .await
+                .unwrap();
+        }
+
+        let mut query = DetectionQuery {
+            object_type: Some("STM32".to_string()),
+            min_confidence: Some(0.4),
+            ..Default::default()
+        };
+        let page = db.search_detections(&query, None).await.unwrap();
+        assert_eq!(page.total, 2);
+
+        query = DetectionQuery {
+            sort: SortField::Confidence,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut confidences = Vec::new();
+        let mut cursor = None;
+        loop {
+            query.cursor = cursor;
+            let page = db
+                .search_detections(&query, query.page_cursor().unwrap().as_ref())
+                .await
+                .unwrap();
+            assert_eq!(page.total, 5);
+            confidences.extend(page.items.iter().map(|record| record.confidence));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        assert_eq!(confidences, vec![0.9, 0.8, 0.7, 0.5, 0.3]);
+
+        query.cursor = Some("not a cursor".to_string());
+        assert!(query.page_cursor().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_sightings_are_merged() {
+        let db = memory_database().await.with_dedup(DedupConfig::default());
+        for request_id in ["r1", "r2", "r3"] {
+            db.insert_detection_request("CAM_1", request_id, "", None).await.unwrap();
+        }
+
+        let first = db.insert_detection("r1", "CAM_1", &[object("STM32", 0.6)]).await.unwrap();
+        let id = first.detection_id.unwrap();
+
+        // Même classe au même endroit : fusion ; autre zone : nouvel objet
+        let mut moved = object("STM32", 0.5);
+        moved.bbox.as_mut().unwrap().x = 50.0;
+        let second = db
+            .insert_detection("r2", "CAM_1", &[object("STM32", 0.8), moved])
+            .await
+            .unwrap();
+        assert_eq!(second.merged_into, vec![id]);
+        assert!(second.detection_id.is_some());
+
+        let third = db.insert_detection("r3", "CAM_1", &[object("STM32", 0.7)]).await.unwrap();
+        assert_eq!(third.detection_id, None);
+
+        let record = db.get_detection(id).await.unwrap().unwrap();
+        assert_eq!(record.ref_count, 3);
+        assert_eq!(record.confidence, 0.8);
+
+        // Un objet suivi qui a peu bougé reste le même ; une autre piste au même endroit est un autre objet
+        for request_id in ["r4", "r5", "r6"] {
+            db.insert_detection_request("CAM_1", request_id, "", None).await.unwrap();
+        }
+        let mut tracked = object("Carte microchip", 0.9);
+        tracked.track_id = Some(7);
+        let entered = db.insert_detection("r4", "CAM_1", &[tracked.clone()]).await.unwrap();
+        tracked.bbox.as_mut().unwrap().x = 1.5;
+        let followed = db.insert_detection("r5", "CAM_1", &[tracked.clone()]).await.unwrap();
+        assert_eq!(followed.merged_into, vec![entered.detection_id.unwrap()]);
+        tracked.track_id = Some(8);
+        let neighbour = db.insert_detection("r6", "CAM_1", &[tracked]).await.unwrap();
+        assert!(neighbour.merged_into.is_empty());
+        assert_eq!(db.get_stats(None).await.unwrap().total_count, 4);
+
+        let totals = db.get_daily_stats(&DailyStatsQuery::default(), &[]).await.unwrap();
+        assert_eq!(totals[0].cadence, 4);
+    }
+
+    #[tokio::test]
+    async fn test_reused_track_id_with_disjoint_boxes_is_not_merged() {
+        let db = memory_database().await.with_dedup(DedupConfig::default());
+        db.insert_detection_request("CAM_1", "r1", "", None).await.unwrap();
+        db.insert_detection_request("CAM_1", "r2", "", None).await.unwrap();
+
+        // Tracker recréé (redémarrage ou caméra inactive) : la piste 1 désigne un nouvel objet
+        let mut before = object("STM32", 0.9);
+        before.track_id = Some(1);
+        let mut after = before.clone();
+        after.bbox.as_mut().unwrap().x = 200.0;
+
+        let first = db.insert_detection("r1", "CAM_1", &[before]).await.unwrap();
+        let second = db.insert_detection("r2", "CAM_1", &[after]).await.unwrap();
+        assert!(second.merged_into.is_empty());
+        assert_ne!(second.detection_id, first.detection_id);
+        assert_eq!(db.get_stats(None).await.unwrap().total_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_detections_without_bbox_stay_separate() {
+        let db = memory_database().await.with_dedup(DedupConfig::default());
+        let first = db.insert_manual_detection("CAM_1", "STM32", "blue", None).await.unwrap();
+        let second = db.insert_manual_detection("CAM_1", "STM32", "blue", None).await.unwrap();
+
+        // Deux saisies identiques dans la fenêtre : deux objets distincts
+        assert_ne!(first.id, second.id);
+        assert_eq!(second.ref_count, 1);
+        assert_eq!(db.get_stats(None).await.unwrap().total_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_reset_clears_detections() {
+        let db = memory_database().await;
+        db.insert_manual_detection("CAM_1", "STM32", "blue", None).await.unwrap();
+        db.reset().await.unwrap();
+
+        assert_eq!(db.get_stats(None).await.unwrap().total_count, 0);
+        assert!(db.get_daily_stats(&DailyStatsQuery::default(), &[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_daily_stats_follow_inserts_and_deletes() {
+        let db = memory_database().await;
+        db.insert_manual_detection("CAM_1", "STM32", "blue", None).await.unwrap();
+        db.insert_manual_detection("CAM_2", "STM32", "blue", None).await.unwrap();
+        let record = db.insert_manual_detection("CAM_1", "Carte microchip", "red", None).await.unwrap();
+
+        let query = DailyStatsQuery::default();
+        let by_type = db.get_daily_stats(&query, &query.group_columns().unwrap()).await.unwrap();
+        let cadences: Vec<_> = by_type
+            .iter()
+            .map(|stat| (stat.object_type.as_deref().unwrap(), stat.cadence))
+            .collect();
+        assert_eq!(cadences, vec![("Carte microchip", 1), ("STM32", 2)]);
+
+        db.delete_detection(record.id).await.unwrap();
+        let totals = db.get_daily_stats(&query, &[]).await.unwrap();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].cadence, 2);
+
+        let invalid = DailyStatsQuery {
+            group_by: Some("type,shape".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.group_columns().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_counting_events_feed_daily_stats() {
+        let db = memory_database().await;
+        let rule = db
+            .insert_counting_rule(&NewCountingRule {
+                g_id: "CAM_1".to_string(),
+                name: "mi-convoyeur".to_string(),
+                kind: RuleKind::Line,
+                points: vec![
+                    crate::counting::Point { x: 0.0, y: 100.0 },
+                    crate::counting::Point { x: 200.0, y: 100.0 },
+                ],
+                direction: LineDirection::In,
+            })
+            .await
+            .unwrap();
+        assert_eq!(db.list_counting_rules(Some("CAM_1")).await.unwrap().len(), 1);
+
+        let crossing = |track_id| CountingEvent {
+            rule_id: rule.id,
+            rule: rule.name.clone(),
+            event: crate::counting::CountingEventKind::In,
+            track_id,
+            class: "STM32".to_string(),
+            color: None,
+        };
+        db.insert_counting_events("CAM_1", &[crossing(1), crossing(2)]).await.unwrap();
+        db.insert_manual_detection("CAM_1", "STM32", "blue", None).await.unwrap();
+
+        // Sans règle : détections brutes ; avec règle : franchissements
+        let raw = db.get_daily_stats(&DailyStatsQuery::default(), &[]).await.unwrap();
+        assert_eq!(raw[0].cadence, 1);
+        let query = DailyStatsQuery {
+            rule: Some("mi-convoyeur".to_string()),
+            group_by: Some("event".to_string()),
+            ..Default::default()
+        };
+        let crossings = db.get_daily_stats(&query, &query.group_columns().unwrap()).await.unwrap();
+        assert_eq!(crossings[0].event.as_deref(), Some("in"));
+        assert_eq!(crossings[0].cadence, 2);
+
+        // Supprimer la règle conserve les comptages passés
+        assert!(db.delete_counting_rule(rule.id).await.unwrap());
+        assert_eq!(db.get_daily_stats(&query, &[]).await.unwrap()[0].cadence, 2);
+    }
+
+    #[tokio::test]
+    async fn test_detections_are_attributed_to_devices() {
+        let db = memory_database().await;
+        let key = devices::generate_api_key();
+        let device = db
+            .insert_device(
+                &NewDevice {
+                    name: "Convoyeur 1".to_string(),
+                    location: Some("Atelier".to_string()),
+                    device_type: "camera".to_string(),
+                    enabled: true,
+                },
+                &key,
+            )
+            .await
+            .unwrap();
+
+        let found = db.find_device_by_key(&devices::hash_api_key(&key)).await.unwrap();
+        assert_eq!(found.map(|found| found.id), Some(device.id));
+
+        let record = db
+            .insert_manual_detection("CAM_1", "STM32", "blue", Some(device.id))
+            .await
+            .unwrap();
+        assert_eq!(record.device_id, Some(device.id));
+        db.insert_manual_detection("CAM_1", "Carte microchip", "red", None).await.unwrap();
+
+        let query = DetectionQuery {
+            device_id: Some(device.id),
+            ..Default::default()
+        };
+        assert_eq!(db.search_detections(&query, None).await.unwrap().total, 1);
+
+        // Après rotation, seule la nouvelle clé est reconnue
+        let rotated = devices::generate_api_key();
+        db.rotate_device_key(device.id, &rotated).await.unwrap();
+        assert!(db.find_device_by_key(&devices::hash_api_key(&key)).await.unwrap().is_none());
+        assert!(db.find_device_by_key(&devices::hash_api_key(&rotated)).await.unwrap().is_some());
+
+        // Supprimer l'appareil conserve ses détections, sans attribution
+        assert!(db.delete_device(device.id).await.unwrap());
+        let record = db.get_detection(record.id).await.unwrap().unwrap();
+        assert_eq!(record.device_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_tracks_device_heartbeats() {
+        let db = memory_database().await;
+        let device = db
+            .insert_device(
+                &NewDevice {
+                    name: "Convoyeur 1".to_string(),
+                    location: None,
+                    device_type: "camera".to_string(),
+                    enabled: true,
+                },
+                &devices::generate_api_key(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(device.status, DeviceStatus::Unknown);
+        assert_eq!(db.devices_health().await.unwrap().unknown, 1);
+
+        let device = db.record_heartbeat(&device).await.unwrap();
+        assert_eq!(device.status, DeviceStatus::Online);
+        assert!(device.last_seen_at.is_some());
+        // Un second battement ne change pas le statut
+        let device = db.record_heartbeat(&device).await.unwrap();
+        assert_eq!(db.device_status_history(device.id, 10).await.unwrap().len(), 1);
+
+        // Récent : reste en ligne
+        assert!(db.mark_offline_devices(60).await.unwrap().is_empty());
+
+        sqlx::query("UPDATE devices SET last_seen_at = datetime('now', '-5 minutes') WHERE id = ?")
+            .bind(device.id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let offline = db.mark_offline_devices(60).await.unwrap();
+        assert_eq!(offline.len(), 1);
+        assert_eq!(offline[0].status, DeviceStatus::Offline);
+        assert!(db.mark_offline_devices(60).await.unwrap().is_empty());
+
+        let health = db.devices_health().await.unwrap();
+        assert_eq!((health.online, health.offline), (0, 1));
+        let offline = db.list_devices(Some(DeviceStatus::Offline)).await.unwrap();
+        assert_eq!(offline.len(), 1);
+        assert_eq!(offline[0].name, "Convoyeur 1");
+        assert!(db.list_devices(Some(DeviceStatus::Online)).await.unwrap().is_empty());
+
+        let history = db.device_status_history(device.id, 10).await.unwrap();
+        let statuses: Vec<DeviceStatus> = history.iter().map(|change| change.status).collect();
+        assert_eq!(statuses, vec![DeviceStatus::Offline, DeviceStatus::Online]);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_token_rotation_and_reuse() {
+        let db = memory_database().await;
+        assert!(db.create_first_admin("admin", "hash").await.unwrap());
+        let ttl = chrono::Duration::days(1);
+        db.create_session("s1", 1, Some("curl"), "h1", ttl).await.unwrap();
+        db.create_session("s2", 1, None, "k1", ttl).await.unwrap();
+        assert!(db.session_is_active("s1").await.unwrap());
+
+        // Rotation : l'ancien jeton est consommé
+        match db.rotate_refresh_token("h1", "h2", ttl).await.unwrap() {
+            RefreshOutcome::Rotated { session_id, user } => {
+                assert_eq!(session_id, "s1");
+                assert_eq!(user.username, "admin");
+            }
+            outcome => panic!("unexpected outcome: {:?}", outcome),
+        }
+        assert!(matches!(db.rotate_refresh_token("unknown", "h3", ttl).await.unwrap(), RefreshOutcome::Invalid));
+
+        // Réutilisation de h1 : toute la famille est révoquée, h2 compris
+        assert!(matches!(
+            db.rotate_refresh_token("h1", "h3", ttl).await.unwrap(),
+            RefreshOutcome::Reused { .. }
+        ));
+        assert!(!db.session_is_active("s1").await.unwrap());
+        assert!(matches!(db.rotate_refresh_token("h2", "h3", ttl).await.unwrap(), RefreshOutcome::Invalid));
+
+        let sessions = db.list_sessions(Some(1)).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, "s2");
+        assert_eq!(sessions[0].username, "admin");
+
+        // Révocation limitée aux sessions du propriétaire
+        assert!(!db.revoke_session("s2", Some(2), RevokeReason::Revoked).await.unwrap());
+        assert_eq!(db.revoke_user_sessions(1, Some("s2"), RevokeReason::PasswordChanged).await.unwrap(), 0);
+        assert!(db.revoke_session("s2", Some(1), RevokeReason::Revoked).await.unwrap());
+        assert!(db.list_sessions(None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_refreshes_rotate_once() {
+        // Base fichier à plusieurs connexions, pour de vrais échanges simultanés
+        let path = std::env::temp_dir().join(format!("refresh_race_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect(&format!("sqlite:{}?mode=rwc", path.display()))
+            .await
+            .unwrap();
+        migrations::run(&pool).await.unwrap();
+        let db = Database::new(pool);
+        assert!(db.create_first_admin("admin", "hash").await.unwrap());
+        let ttl = chrono::Duration::days(1);
+        db.create_session("s1", 1, None, "h1", ttl).await.unwrap();
+
+        let (first, second) = tokio::join!(
+            db.rotate_refresh_token("h1", "h2", ttl),
+            db.rotate_refresh_token("h1", "h3", ttl)
+        );
+        let outcomes = [first.unwrap(), second.unwrap()];
+        // Un seul échange réussit, l'autre est traité comme une réutilisation
+        assert_eq!(outcomes.iter().filter(|o| matches!(o, RefreshOutcome::Rotated { .. })).count(), 1);
+        assert_eq!(outcomes.iter().filter(|o| matches!(o, RefreshOutcome::Reused { .. })).count(), 1);
+        assert!(!db.session_is_active("s1").await.unwrap());
+
+        db.pool.close().await;
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn test_throughput_fills_empty_buckets() {
+        let db = memory_database().await;
+        for (request_id, timestamp, class) in [
+            ("r1", "2024-01-15 10:00:10", "STM32"),
+            ("r2", "2024-01-15 10:00:40", "STM32"),
+            ("r3", "2024-01-15 10:02:05", "Carte microchip"),
+        ] {
+            db.insert_detection_request("CAM_1", request_id, "", None).await.unwrap();
+            db.insert_detection(request_id, "CAM_1", &[object(class, 0.9)]).await.unwrap();
+            sqlx::query("UPDATE detections SET timestamp = ? WHERE request_id = ?")
+                .bind(timestamp)
+                .bind(request_id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        let query = ThroughputQuery {
+            from: Some("2024-01-15T10:00".to_string()),
+            to: Some("2024-01-15 10:03:00".to_string()),
+            ..Default::default()
+        };
+        let (from, to) = query.range().unwrap();
+        let throughput = db.get_throughput(&query, from, to).await.unwrap();
+
+        assert_eq!(throughput.buckets.len(), 4);
+        assert_eq!(throughput.buckets[0], "2024-01-15 10:00:00");
+        assert_eq!(throughput.series.len(), 2);
+        assert_eq!(throughput.series[0].object_type, "Carte microchip");
+        assert_eq!(throughput.series[0].counts, vec![0, 0, 1, 0]);
+        assert_eq!(throughput.series[1].counts, vec![2, 0, 0, 0]);
+        assert_eq!(throughput.series[1].total, 2);
+    }
+
+    #[tokio::test]
+    async fn test_throughput_includes_whole_last_bucket() {
+        let db = memory_database().await;
+        for (request_id, timestamp) in [("r1", "2024-01-15 10:05:00"), ("r2", "2024-01-15 10:40:00")] {
+            db.insert_detection_request("CAM_1", request_id, "", None).await.unwrap();
+            db.insert_detection(request_id, "CAM_1", &[object("STM32", 0.9)]).await.unwrap();
+            sqlx::query("UPDATE detections SET timestamp = ? WHERE request_id = ?")
+                .bind(timestamp)
+                .bind(request_id)
+                .execute(&db.pool)
+                .await
+                .unwrap();
+        }
+
+        // "to" au milieu de l'heure : le dernier intervalle est compté en entier
+        let query = ThroughputQuery {
+            from: Some("2024-01-15 09:00".to_string()),
+            to: Some("2024-01-15 10:15".to_string()),
+            interval: Interval::Hour,
+            ..Default::default()
+        };
+        let (from, to) = query.range().unwrap();
+        let throughput = db.get_throughput(&query, from, to).await.unwrap();
+
+        assert_eq!(throughput.buckets, vec!["2024-01-15 09:00:00", "2024-01-15 10:00:00"]);
+        assert_eq!(throughput.series[0].counts, vec![0, 2]);
+    }
+
+    #[test]
+    fn test_throughput_range_validation() {
+        let query = ThroughputQuery {
+            from: Some("2024-01-01".to_string()),
+            to: Some("2024-02-01".to_string()),
+            ..Default::default()
+        };
+        assert!(query.range().is_err());
+
+        let query = ThroughputQuery {
+            interval: Interval::Hour,
+            ..query
+        };
+        assert!(query.range().is_ok());
+        assert!(parse_datetime("15/01/2024").is_none());
+    }
+}
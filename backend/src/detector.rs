@@ -0,0 +1,125 @@
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Detection {
+    pub class: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub confidence: f32,
+    pub bbox: BoundingBox,
+    // Identifiant de piste attribué par le tracker (détections avec g_id)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub track_id: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BoundingBox {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+// Paramètres communs à toutes les détections
+#[derive(Debug, Clone)]
+pub struct DetectionParams {
+    pub confidence_threshold: f32,
+}
+
+// Description d'un modèle exposée par /models
+#[derive(Serialize, Debug, Clone)]
+pub struct DetectorInfo {
+    pub name: String,
+    pub description: String,
+    pub classes: Vec<String>,
+}
+
+// Moteur de détection interchangeable
+pub trait Detector: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn describe(&self) -> DetectorInfo;
+
+    fn detect(&self, image: &DynamicImage, params: &DetectionParams) -> Result<Vec<Detection>, String>;
+}
+
+// Registre des détecteurs chargés au démarrage
+#[derive(Clone)]
+pub struct DetectorRegistry {
+    detectors: BTreeMap<String, Arc<dyn Detector>>,
+    default_name: String,
+}
+
+impl DetectorRegistry {
+    // Le premier détecteur enregistré devient le modèle par défaut
+    pub fn new() -> Self {
+        Self {
+            detectors: BTreeMap::new(),
+            default_name: String::new(),
+        }
+    }
+
+    pub fn register(&mut self, detector: Arc<dyn Detector>) {
+        let name = detector.name().to_string();
+        if self.detectors.is_empty() {
+            self.default_name = name.clone();
+        }
+        println!("🧠 Modèle chargé: {}", name);
+        self.detectors.insert(name, detector);
+    }
+
+    pub fn default_name(&self) -> &str {
+        &self.default_name
+    }
+
+    // Sélectionner un détecteur, ou le modèle par défaut si aucun nom n'est fourni
+    pub fn get(&self, name: Option<&str>) -> Option<Arc<dyn Detector>> {
+        let name = name.unwrap_or(&self.default_name);
+        self.detectors.get(name).cloned()
+    }
+
+    pub fn list(&self) -> Vec<DetectorInfo> {
+        self.detectors.values().map(|detector| detector.describe()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct StubDetector(&'static str);
+
+    impl Detector for StubDetector {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn describe(&self) -> DetectorInfo {
+            DetectorInfo {
+                name: self.0.to_string(),
+                description: "stub".to_string(),
+                classes: Vec::new(),
+            }
+        }
+
+        fn detect(&self, _image: &DynamicImage, _params: &DetectionParams) -> Result<Vec<Detection>, String> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_registry_selects_by_name_and_default() {
+        let mut registry = DetectorRegistry::new();
+        registry.register(Arc::new(StubDetector("color")));
+        registry.register(Arc::new(StubDetector("yolo")));
+
+        assert_eq!(registry.default_name(), "color");
+        assert_eq!(registry.get(None).unwrap().name(), "color");
+        assert_eq!(registry.get(Some("yolo")).unwrap().name(), "yolo");
+        assert!(registry.get(Some("unknown")).is_none());
+        assert_eq!(registry.list().len(), 2);
+    }
+}
@@ -0,0 +1,354 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+use crate::auth::{self, reject, Rejection, Role, UserInfo};
+use crate::database::Database;
+
+// En-tête portant la clé d'API d'un appareil
+pub const API_KEY_HEADER: &str = "x-api-key";
+// Préfixe des clés, pour les reconnaître dans les logs et les fichiers de configuration
+const API_KEY_PREFIX: &str = "dk_";
+// Nombre de caractères de la clé conservés en clair pour l'identifier dans la liste
+const VISIBLE_KEY_CHARS: usize = 8;
+
+const DEVICE_TYPES: &[&str] = &["camera", "script", "sensor"];
+
+// Délai sans nouvelles avant de passer un appareil hors ligne (surchargeable via DEVICE_OFFLINE_SECS)
+const DEFAULT_OFFLINE_SECS: u64 = 120;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceStatus {
+    // Jamais vu depuis sa création
+    #[default]
+    Unknown,
+    Online,
+    Offline,
+}
+
+impl DeviceStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeviceStatus::Unknown => "unknown",
+            DeviceStatus::Online => "online",
+            DeviceStatus::Offline => "offline",
+        }
+    }
+
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "online" => DeviceStatus::Online,
+            "offline" => DeviceStatus::Offline,
+            _ => DeviceStatus::Unknown,
+        }
+    }
+}
+
+// Caméra ou script autorisé à envoyer des détections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    pub id: i64,
+    pub name: String,
+    pub location: Option<String>,
+    #[serde(rename = "type")]
+    pub device_type: String,
+    pub enabled: bool,
+    // Début de la clé courante (la clé complète n'est jamais stockée)
+    pub key_prefix: String,
+    pub status: DeviceStatus,
+    pub last_seen_at: Option<String>,
+    pub created_at: String,
+}
+
+// Changement de statut, table device_status_history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceStatusChange {
+    pub status: DeviceStatus,
+    pub changed_at: String,
+}
+
+// Résumé des appareils actifs exposé par /health
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DevicesHealth {
+    pub online: i64,
+    pub offline: i64,
+    pub unknown: i64,
+}
+
+// Appareil accompagné de sa clé, renvoyé uniquement à la création et à la rotation
+#[derive(Debug, Serialize)]
+pub struct DeviceWithKey {
+    #[serde(flatten)]
+    pub device: Device,
+    pub api_key: String,
+}
+
+// Corps de POST /api/devices
+#[derive(Debug, Deserialize)]
+pub struct NewDevice {
+    pub name: String,
+    pub location: Option<String>,
+    #[serde(rename = "type", default = "default_device_type")]
+    pub device_type: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_device_type() -> String {
+    "camera".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// Corps de PUT /api/devices/:id, seuls les champs fournis sont modifiés
+#[derive(Debug, Default, Deserialize)]
+pub struct DeviceUpdate {
+    pub name: Option<String>,
+    pub location: Option<String>,
+    #[serde(rename = "type")]
+    pub device_type: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+fn validate_type(device_type: &str) -> Result<(), String> {
+    if DEVICE_TYPES.contains(&device_type) {
+        Ok(())
+    } else {
+        Err(format!("Invalid device type: {} (expected one of {})", device_type, DEVICE_TYPES.join(", ")))
+    }
+}
+
+impl NewDevice {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("name is required".to_string());
+        }
+        validate_type(&self.device_type)
+    }
+}
+
+impl DeviceUpdate {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            return Err("name cannot be empty".to_string());
+        }
+        self.device_type.as_deref().map_or(Ok(()), validate_type)
+    }
+}
+
+// Nouvelle clé d'API (244 bits aléatoires issus de deux UUID v4)
+pub fn generate_api_key() -> String {
+    format!(
+        "{}{}{}",
+        API_KEY_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+// Les clés sont longues et aléatoires : un SHA-256 suffit pour les stocker
+pub fn hash_api_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+pub fn key_prefix(key: &str) -> String {
+    key.chars().take(API_KEY_PREFIX.len() + VISIBLE_KEY_CHARS).collect()
+}
+
+// Auteur d'une requête de détection : appareil (X-API-Key) ou utilisateur (JWT)
+#[derive(Debug)]
+pub enum Caller {
+    User(UserInfo),
+    Device(Device),
+}
+
+impl Caller {
+    pub fn device(&self) -> Option<&Device> {
+        match self {
+            Caller::Device(device) => Some(device),
+            _ => None,
+        }
+    }
+
+    // Nom affiché dans les logs
+    pub fn label(&self) -> &str {
+        match self {
+            Caller::User(user) => &user.username,
+            Caller::Device(device) => &device.name,
+        }
+    }
+}
+
+// Appareil actif ou utilisateur d'au moins le rôle "user" ; sans identifiants : 401
+#[async_trait]
+impl<S> FromRequestParts<S> for Caller
+where
+    Database: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(key) = parts.headers.get(API_KEY_HEADER) {
+            let key = key
+                .to_str()
+                .map_err(|_| reject(StatusCode::UNAUTHORIZED, "Invalid API key"))?;
+            let db = Database::from_ref(state);
+            let device = db
+                .find_device_by_key(&hash_api_key(key))
+                .await
+                .map_err(|e| {
+                    eprintln!("❌ Erreur base de données: {}", e);
+                    reject(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+                })?
+                .ok_or_else(|| reject(StatusCode::UNAUTHORIZED, "Invalid API key"))?;
+
+            if !device.enabled {
+                println!("⛔ Appareil désactivé: {}", device.name);
+                return Err(reject(StatusCode::FORBIDDEN, "Device is disabled"));
+            }
+
+            // Toute requête signée par l'appareil vaut battement de cœur
+            let device = db.record_heartbeat(&device).await.map_err(|e| {
+                eprintln!("❌ Erreur base de données: {}", e);
+                reject(StatusCode::INTERNAL_SERVER_ERROR, "Database error")
+            })?;
+            return Ok(Caller::Device(device));
+        }
+
+        let token = auth::bearer_token(&parts.headers)
+            .ok_or_else(|| reject(StatusCode::UNAUTHORIZED, "Missing API key or bearer token"))?;
+        let (user, _) = auth::authenticate(&Database::from_ref(state), token).await?;
+        auth::check_role(&user, Role::User, auth::request_path(parts))?;
+        Ok(Caller::User(user))
+    }
+}
+
+// Surveillance des battements de cœur
+#[derive(Debug, Clone, Copy)]
+pub struct HeartbeatConfig {
+    pub offline_after: Duration,
+}
+
+impl HeartbeatConfig {
+    pub fn from_env() -> Self {
+        let secs = std::env::var("DEVICE_OFFLINE_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .filter(|secs| *secs > 0)
+            .unwrap_or(DEFAULT_OFFLINE_SECS);
+        Self {
+            offline_after: Duration::from_secs(secs),
+        }
+    }
+
+    // Vérifier quatre fois par délai, entre 1 s et 1 min
+    fn check_interval(&self) -> Duration {
+        (self.offline_after / 4).clamp(Duration::from_secs(1), Duration::from_secs(60))
+    }
+}
+
+// Tâche de fond : passe hors ligne les appareils silencieux depuis offline_after
+pub async fn watch_heartbeats(db: Database, config: HeartbeatConfig) {
+    let mut interval = tokio::time::interval(config.check_interval());
+    loop {
+        interval.tick().await;
+        match db.mark_offline_devices(config.offline_after.as_secs()).await {
+            Ok(devices) => {
+                for device in devices {
+                    println!("🔴 Appareil hors ligne: {} (dernier signe de vie: {})",
+                        device.name,
+                        device.last_seen_at.as_deref().unwrap_or("jamais"));
+                }
+            }
+            Err(e) => eprintln!("❌ Surveillance des appareils impossible: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[test]
+    fn test_api_keys_are_unique_and_hashed() {
+        let key = generate_api_key();
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert_eq!(key.len(), API_KEY_PREFIX.len() + 64);
+        assert_ne!(key, generate_api_key());
+
+        assert_eq!(hash_api_key(&key), hash_api_key(&key));
+        assert_ne!(hash_api_key(&key), key);
+        assert_eq!(key_prefix(&key), key[..11]);
+    }
+
+    #[test]
+    fn test_validates_devices() {
+        let device = |name: &str, device_type: &str| NewDevice {
+            name: name.to_string(),
+            location: None,
+            device_type: device_type.to_string(),
+            enabled: true,
+        };
+        assert!(device("Convoyeur 1", "camera").validate().is_ok());
+        assert!(device(" ", "camera").validate().is_err());
+        assert!(device("Convoyeur 1", "drone").validate().is_err());
+
+        let update = DeviceUpdate {
+            name: Some(String::new()),
+            ..Default::default()
+        };
+        assert!(update.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_callers_are_rejected() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        migrations::run(&pool).await.unwrap();
+        let db = Database::new(pool);
+
+        let caller = |key: Option<&str>| {
+            let mut request = axum::http::Request::builder().method("POST").uri("/api/detections");
+            if let Some(key) = key {
+                request = request.header(API_KEY_HEADER, key);
+            }
+            request.body(()).unwrap().into_parts().0
+        };
+
+        // Sans clé d'appareil ni token : 401
+        let (status, _) = Caller::from_request_parts(&mut caller(None), &db).await.unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _) = Caller::from_request_parts(&mut caller(Some("dk_unknown")), &db)
+            .await
+            .unwrap_err();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let key = generate_api_key();
+        let device = NewDevice {
+            name: "Convoyeur 1".to_string(),
+            location: None,
+            device_type: "camera".to_string(),
+            enabled: true,
+        };
+        db.insert_device(&device, &key).await.unwrap();
+        let accepted = Caller::from_request_parts(&mut caller(Some(&key)), &db)
+            .await
+            .map_err(|(status, _)| status)
+            .unwrap();
+        assert_eq!(accepted.label(), "Convoyeur 1");
+    }
+}
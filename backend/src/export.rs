@@ -0,0 +1,259 @@
+use axum::body::{Body, Bytes};
+use chrono::NaiveDate;
+use futures_util::stream;
+use serde::Deserialize;
+use std::io;
+use tokio::sync::mpsc;
+
+use crate::database::{DetectionQuery, DetectionRecord};
+
+const CSV_HEADER: &str = "id,g_id,request_id,type,color,confidence,datetime,ref_count,objects";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+    Ndjson,
+    Txt,
+}
+
+impl ExportFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Txt => "text/plain; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Txt => "txt",
+        }
+    }
+}
+
+// Paramètres propres à /api/download (les filtres sont ceux de l'historique)
+#[derive(Debug, Default, Deserialize)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+// Titre du rapport TXT : période et filtres appliqués
+pub fn report_title(query: &DetectionQuery) -> String {
+    let date = |date: Option<NaiveDate>| date.map(|date| date.to_string()).unwrap_or_else(|| "…".to_string());
+    let mut title = format!("Période: {} → {}", date(query.from_date), date(query.to_date));
+
+    let filters = [
+        ("g_id", query.g_id.clone()),
+        ("type", query.object_type.clone()),
+        ("couleur", query.color.clone()),
+        ("confiance min", query.min_confidence.map(|value| value.to_string())),
+        ("statut", query.status.clone()),
+        ("appareil", query.device_id.map(|id| id.to_string())),
+    ];
+    for (name, value) in filters {
+        if let Some(value) = value {
+            title.push_str(&format!("\n{}: {}", name, value));
+        }
+    }
+    title
+}
+
+// Champ CSV selon la RFC 4180 : guillemets si nécessaire, guillemets internes doublés
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Mise en forme incrémentale d'un export : en-tête, une entrée par détection, pied
+pub struct ExportWriter {
+    format: ExportFormat,
+    // Titre du rapport TXT (période et filtres)
+    title: String,
+    count: usize,
+}
+
+impl ExportWriter {
+    pub fn new(format: ExportFormat, title: String) -> Self {
+        Self {
+            format,
+            title,
+            count: 0,
+        }
+    }
+
+    pub fn header(&self) -> String {
+        match self.format {
+            ExportFormat::Csv => format!("{}\r\n", CSV_HEADER),
+            ExportFormat::Json => "[".to_string(),
+            ExportFormat::Ndjson => String::new(),
+            ExportFormat::Txt => format!(
+                "RAPPORT DE DÉTECTIONS\n{}\nGénéré le: {}\n{}\n\n",
+                self.title,
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                "=".repeat(60)
+            ),
+        }
+    }
+
+    pub fn record(&mut self, record: &DetectionRecord) -> String {
+        self.count += 1;
+
+        match self.format {
+            ExportFormat::Csv => {
+                let fields = [
+                    record.id.to_string(),
+                    csv_field(&record.g_id),
+                    csv_field(&record.request_id),
+                    csv_field(&record.object_type),
+                    csv_field(&record.color),
+                    record.confidence.to_string(),
+                    csv_field(&record.datetime),
+                    record.ref_count.to_string(),
+                    record.objects.len().to_string(),
+                ];
+                format!("{}\r\n", fields.join(","))
+            }
+            ExportFormat::Json => {
+                let separator = if self.count == 1 { "\n" } else { ",\n" };
+                format!("{}{}", separator, serde_json::to_string(record).unwrap_or_default())
+            }
+            ExportFormat::Ndjson => format!("{}\n", serde_json::to_string(record).unwrap_or_default()),
+            ExportFormat::Txt => format!(
+                "[{}] #{} {} - {} ({}) - confiance {:.0}% - ref {}\n",
+                record.datetime,
+                record.id,
+                record.g_id,
+                record.object_type,
+                record.color,
+                record.confidence * 100.0,
+                record.ref_count
+            ),
+        }
+    }
+
+    pub fn footer(&self) -> String {
+        match self.format {
+            ExportFormat::Json if self.count > 0 => "\n]\n".to_string(),
+            ExportFormat::Json => "]\n".to_string(),
+            ExportFormat::Txt => format!("\n{}\nTotal: {} détection(s)\n", "=".repeat(60), self.count),
+            ExportFormat::Csv | ExportFormat::Ndjson => String::new(),
+        }
+    }
+}
+
+enum Phase {
+    Header,
+    Records,
+    Done,
+}
+
+// Corps HTTP écrit au fil de la lecture en base ; une erreur SQL interrompt le transfert
+pub fn stream_body(
+    records: mpsc::Receiver<Result<DetectionRecord, sqlx::Error>>,
+    writer: ExportWriter,
+) -> Body {
+    let chunks = stream::unfold(
+        (records, writer, Phase::Header),
+        |(mut records, mut writer, phase)| async move {
+            match phase {
+                Phase::Header => {
+                    let chunk = Bytes::from(writer.header());
+                    Some((Ok(chunk), (records, writer, Phase::Records)))
+                }
+                Phase::Records => match records.recv().await {
+                    Some(Ok(record)) => {
+                        let chunk = Bytes::from(writer.record(&record));
+                        Some((Ok(chunk), (records, writer, Phase::Records)))
+                    }
+                    Some(Err(e)) => {
+                        eprintln!("❌ Export interrompu: {}", e);
+                        Some((Err(io::Error::other(e)), (records, writer, Phase::Done)))
+                    }
+                    None => {
+                        let chunk = Bytes::from(writer.footer());
+                        Some((Ok(chunk), (records, writer, Phase::Done)))
+                    }
+                },
+                Phase::Done => None,
+            }
+        },
+    );
+
+    Body::from_stream(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: i64, g_id: &str) -> DetectionRecord {
+        DetectionRecord {
+            id,
+            request_id: format!("r{}", id),
+            g_id: g_id.to_string(),
+            object_type: "STM32".to_string(),
+            color: "blue".to_string(),
+            confidence: 0.5,
+            datetime: "2024-01-15 10:30:00".to_string(),
+            ref_count: 1,
+            device_id: None,
+            objects: Vec::new(),
+        }
+    }
+
+    fn export(format: ExportFormat, records: &[DetectionRecord]) -> String {
+        let mut writer = ExportWriter::new(format, String::new());
+        let mut output = writer.header();
+        for record in records {
+            output.push_str(&writer.record(record));
+        }
+        output.push_str(&writer.footer());
+        output
+    }
+
+    #[test]
+    fn test_csv_escapes_fields() {
+        assert_eq!(csv_field("CAM_1"), "CAM_1");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\"\n"), "\"say \"\"hi\"\"\n\"");
+
+        let output = export(ExportFormat::Csv, &[record(1, "CAM \"1\", hall")]);
+        let lines: Vec<&str> = output.split("\r\n").collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert!(lines[1].starts_with("1,\"CAM \"\"1\"\", hall\",r1,STM32,"));
+    }
+
+    #[test]
+    fn test_json_and_ndjson_are_valid() {
+        for count in [0, 1, 3] {
+            let records: Vec<_> = (0..count).map(|id| record(id, "CAM_1")).collect();
+            let json: serde_json::Value = serde_json::from_str(&export(ExportFormat::Json, &records)).unwrap();
+            assert_eq!(json.as_array().unwrap().len(), count as usize);
+        }
+
+        let ndjson = export(ExportFormat::Ndjson, &[record(1, "a"), record(2, "b")]);
+        assert_eq!(ndjson.lines().count(), 2);
+        assert!(ndjson
+            .lines()
+            .all(|line| serde_json::from_str::<serde_json::Value>(line).is_ok()));
+    }
+
+    #[test]
+    fn test_txt_report_counts_records() {
+        let output = export(ExportFormat::Txt, &[record(1, "CAM_1"), record(2, "CAM_1")]);
+        assert!(output.contains("#2 CAM_1 - STM32 (blue) - confiance 50%"));
+        assert!(output.ends_with("Total: 2 détection(s)\n"));
+    }
+}
@@ -0,0 +1,82 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue, Response},
+    Router,
+};
+use std::path::PathBuf;
+use tower_http::{
+    services::{ServeDir, ServeFile},
+    set_header::SetResponseHeaderLayer,
+};
+
+use crate::AppState;
+
+// Dossier servi par défaut (lancement depuis backend/)
+const DEFAULT_FRONTEND_DIR: &str = "../frontend";
+
+// Chemin du frontend, surchargeable via FRONTEND_DIR
+pub fn frontend_dir() -> PathBuf {
+    std::env::var("FRONTEND_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_FRONTEND_DIR))
+}
+
+// Client qui demande explicitement du JSON (ancien health check sur "/") plutôt qu'une page
+pub fn wants_json(headers: &HeaderMap) -> bool {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    accept.contains("application/json") && !accept.contains("text/html")
+}
+
+// Les pages HTML sont revalidées à chaque visite, les assets sont mis en cache 1h
+fn cache_control<B>(response: &Response<B>) -> Option<HeaderValue> {
+    if !response.status().is_success() {
+        return None;
+    }
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.starts_with("text/html"))
+        .unwrap_or(false);
+
+    if is_html {
+        Some(HeaderValue::from_static("no-cache"))
+    } else {
+        Some(HeaderValue::from_static("public, max-age=3600"))
+    }
+}
+
+// Service des fichiers statiques avec repli sur index.html
+pub fn router(dir: PathBuf) -> Router<AppState> {
+    let index = dir.join("index.html");
+    let serve_dir = ServeDir::new(&dir).fallback(ServeFile::new(index));
+
+    Router::new()
+        .fallback_service(serve_dir)
+        .layer(SetResponseHeaderLayer::if_not_present(
+            header::CACHE_CONTROL,
+            cache_control,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accept(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_detects_json_clients() {
+        assert!(wants_json(&accept("application/json")));
+        assert!(!wants_json(&accept("text/html,application/xhtml+xml,application/json;q=0.9")));
+        assert!(!wants_json(&accept("*/*")));
+        assert!(!wants_json(&HeaderMap::new()));
+    }
+}
@@ -0,0 +1,165 @@
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::io::{Limits, Reader as ImageReader};
+use image::{DynamicImage, ImageFormat};
+use std::fmt;
+use std::io::Cursor;
+
+// Taille maximale d'une image encodée (10 Mo)
+pub const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+// Limite du corps HTTP des routes de détection (base64 + marge pour le JSON/multipart)
+pub const MAX_BODY_BYTES: usize = MAX_IMAGE_BYTES / 3 * 4 + 64 * 1024;
+// Dimension maximale acceptée, pour éviter les bombes de décompression
+const MAX_IMAGE_SIDE: u32 = 8192;
+
+const SUPPORTED_FORMATS: &[ImageFormat] = &[
+    ImageFormat::Png,
+    ImageFormat::Jpeg,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+];
+
+// Erreurs de lecture d'une image envoyée au backend
+#[derive(Debug)]
+pub enum ImageInputError {
+    Empty,
+    InvalidDataUrl,
+    InvalidBase64(base64::DecodeError),
+    TooLarge { size: usize, max: usize },
+    UnsupportedFormat,
+    Decode(image::ImageError),
+}
+
+impl ImageInputError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ImageInputError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            ImageInputError::UnsupportedFormat => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            _ => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl fmt::Display for ImageInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageInputError::Empty => write!(f, "Image data is empty"),
+            ImageInputError::InvalidDataUrl => write!(f, "Malformed data URL, expected data:image/...;base64,"),
+            ImageInputError::InvalidBase64(e) => write!(f, "Invalid base64 image data: {}", e),
+            ImageInputError::TooLarge { size, max } => {
+                write!(f, "Image too large: {} bytes (max {} bytes)", size, max)
+            }
+            ImageInputError::UnsupportedFormat => write!(f, "Unsupported image format (PNG, JPEG, WebP or BMP expected)"),
+            ImageInputError::Decode(e) => write!(f, "Invalid image: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ImageInputError {}
+
+// Décoder une image envoyée en base64 brut ou en data URL
+pub fn decode_base64_image(input: &str) -> Result<DynamicImage, ImageInputError> {
+    let input = input.trim();
+    let payload = match input.strip_prefix("data:") {
+        Some(data_url) => {
+            let (header, payload) = data_url.split_once(',').ok_or(ImageInputError::InvalidDataUrl)?;
+            if !header.starts_with("image/") || !header.ends_with(";base64") {
+                return Err(ImageInputError::InvalidDataUrl);
+            }
+            payload
+        }
+        None => input,
+    };
+
+    // Vérifier la taille avant de décoder
+    let estimated_size = payload.len() / 4 * 3;
+    if estimated_size > MAX_IMAGE_BYTES {
+        return Err(ImageInputError::TooLarge {
+            size: estimated_size,
+            max: MAX_IMAGE_BYTES,
+        });
+    }
+
+    let cleaned: String = payload.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+    let bytes = STANDARD
+        .decode(cleaned)
+        .map_err(ImageInputError::InvalidBase64)?;
+
+    decode_image_bytes(&bytes)
+}
+
+// Décoder une image PNG, JPEG, WebP ou BMP
+pub fn decode_image_bytes(bytes: &[u8]) -> Result<DynamicImage, ImageInputError> {
+    if bytes.is_empty() {
+        return Err(ImageInputError::Empty);
+    }
+
+    if bytes.len() > MAX_IMAGE_BYTES {
+        return Err(ImageInputError::TooLarge {
+            size: bytes.len(),
+            max: MAX_IMAGE_BYTES,
+        });
+    }
+
+    let format = image::guess_format(bytes).map_err(|_| ImageInputError::UnsupportedFormat)?;
+    if !SUPPORTED_FORMATS.contains(&format) {
+        return Err(ImageInputError::UnsupportedFormat);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIDE);
+    limits.max_image_height = Some(MAX_IMAGE_SIDE);
+
+    let mut reader = ImageReader::with_format(Cursor::new(bytes), format);
+    reader.limits(limits);
+    reader.decode().map_err(ImageInputError::Decode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn encoded_png() -> Vec<u8> {
+        let image = RgbImage::from_pixel(4, 3, Rgb([200, 10, 10]));
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(image)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_decodes_raw_base64_and_data_url() {
+        let encoded = STANDARD.encode(encoded_png());
+
+        let image = decode_base64_image(&encoded).unwrap();
+        assert_eq!((image.width(), image.height()), (4, 3));
+
+        let data_url = format!("data:image/png;base64,{}", encoded);
+        assert!(decode_base64_image(&data_url).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_malformed_input() {
+        assert!(matches!(
+            decode_base64_image("data:text/plain,hello"),
+            Err(ImageInputError::InvalidDataUrl)
+        ));
+        assert!(matches!(
+            decode_base64_image("not base64 !!"),
+            Err(ImageInputError::InvalidBase64(_))
+        ));
+        assert!(matches!(
+            decode_base64_image(&STANDARD.encode(b"GIF89a......")),
+            Err(ImageInputError::UnsupportedFormat)
+        ));
+    }
+
+    #[test]
+    fn test_rejects_oversized_payload() {
+        let payload = "A".repeat(MAX_IMAGE_BYTES / 3 * 4 + 8);
+        let err = decode_base64_image(&payload).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
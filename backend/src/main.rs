@@ -1,47 +1,102 @@
+mod api;
+mod auth;
+mod color_detector;
+mod counting;
+mod database;
+mod detector;
+mod devices;
+mod export;
+mod frontend;
+mod image_input;
+mod jwt_keys;
+mod migrations;
+#[cfg(feature = "onnx")]
+mod onnx_detector;
+mod passwords;
+mod postprocess;
+mod sessions;
+mod tracker;
+mod users;
+mod xlsx_export;
+
 use axum::{
-    extract::Multipart,
+    extract::{DefaultBodyLimit, FromRef, Multipart, Request, State},
     http::StatusCode,
-    response::{IntoResponse, Json},
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use tower_http::cors::{CorsLayer, Any};
 use tower::ServiceBuilder;
-use tokio;
+
+use auth::{Authorized, Viewer};
+use color_detector::ColorDetector;
+use counting::CountingEvent;
+use database::{Database, DedupConfig, DetectedObject};
+use devices::{Caller, HeartbeatConfig};
+use detector::{Detection, DetectionParams, Detector, DetectorRegistry};
+use image::DynamicImage;
+use postprocess::PostProcessOptions;
+use tracker::{TrackEvent, TrackerConfig, TrackerRegistry};
+use image_input::{decode_base64_image, decode_image_bytes, ImageInputError, MAX_BODY_BYTES};
+
+// État partagé entre les handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Database,
+    pub detectors: DetectorRegistry,
+    pub trackers: TrackerRegistry,
+}
+
+impl FromRef<AppState> for Database {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for DetectorRegistry {
+    fn from_ref(state: &AppState) -> Self {
+        state.detectors.clone()
+    }
+}
+
+impl FromRef<AppState> for TrackerRegistry {
+    fn from_ref(state: &AppState) -> Self {
+        state.trackers.clone()
+    }
+}
 
 // Structures pour les requêtes et réponses
 #[derive(Serialize, Deserialize, Debug)]
 struct DetectionRequest {
     image_data: Option<String>, // base64 encoded image
+    g_id: Option<String>,
     model_type: Option<String>,
     confidence: Option<f32>,
+    #[serde(flatten)]
+    postprocess: PostProcessOptions,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 struct DetectionResponse {
     success: bool,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
     detections: Option<Vec<Detection>>,
+    // Entrées et sorties de pistes (uniquement avec un g_id)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    events: Option<Vec<TrackEvent>>,
+    // Entrées / sorties de zones et franchissements de lignes (règles du g_id)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    counting_events: Option<Vec<CountingEvent>>,
     processing_time: Option<f32>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Detection {
-    class: String,
-    confidence: f32,
-    bbox: BoundingBox,
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct BoundingBox {
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-}
-
 // Handler pour la route de base
 async fn root() -> impl IntoResponse {
     Json(HashMap::from([
@@ -51,83 +106,279 @@ async fn root() -> impl IntoResponse {
     ]))
 }
 
-// Handler pour vérifier la santé de l'API
-async fn health_check() -> impl IntoResponse {
+// "/" sert le dashboard ; les clients qui demandent du JSON (Accept: application/json)
+// reçoivent toujours le statut de l'API, comme avant l'arrivée du frontend
+async fn json_root(request: Request, next: Next) -> Response {
+    if request.uri().path() == "/" && frontend::wants_json(request.headers()) {
+        return root().await.into_response();
+    }
+    next.run(request).await
+}
+
+// Handler pour vérifier la santé de l'API (dégradée si un appareil est hors ligne)
+async fn health_check(State(db): State<Database>) -> impl IntoResponse {
     let timestamp = chrono::Utc::now().to_rfc3339();
+    let devices = db.devices_health().await.unwrap_or_else(|e| {
+        eprintln!("❌ Statut des appareils indisponible: {}", e);
+        Default::default()
+    });
+    let status = if devices.offline > 0 { "degraded" } else { "healthy" };
     Json(serde_json::json!({
-        "status": "healthy",
-        "timestamp": timestamp
+        "status": status,
+        "timestamp": timestamp,
+        "devices": devices
     }))
 }
 
-// Handler principal pour la détection d'objets (avec JSON)
-async fn detect_objects_json(
-    Json(payload): Json<DetectionRequest>
-) -> impl IntoResponse {
-    println!("Received detection request: {:?}", payload);
+// Sélectionner le modèle demandé (404 si inconnu)
+#[allow(clippy::result_large_err)]
+fn select_detector(
+    registry: &DetectorRegistry,
+    model_type: Option<&str>,
+) -> Result<Arc<dyn Detector>, (StatusCode, Json<DetectionResponse>)> {
+    registry.get(model_type).ok_or_else(|| {
+        detection_error(
+            StatusCode::NOT_FOUND,
+            format!("Unknown model: {}", model_type.unwrap_or_default()),
+        )
+    })
+}
+
+// Décoder l'image et exécuter la détection hors du runtime async
+#[allow(clippy::result_large_err)]
+async fn run_detection(
+    detector: Arc<dyn Detector>,
+    source: ImageSource,
+    params: DetectionParams,
+    options: PostProcessOptions,
+) -> Result<Vec<Detection>, (StatusCode, Json<DetectionResponse>)> {
+    let model_name = detector.name().to_string();
+    let failed = |model_name: &str, e: String| {
+        eprintln!("Detection failed with model {}: {}", model_name, e);
+        detection_error(StatusCode::INTERNAL_SERVER_ERROR, format!("Detection failed: {}", e))
+    };
     
-    // Simulation de traitement (remplacez par votre logique réelle)
-    let start_time = std::time::Instant::now();
+    let task_model = model_name.clone();
+    tokio::task::spawn_blocking(move || {
+        // Le décodage (jusqu'à 8192² pixels) est aussi coûteux que l'inférence
+        let image = source.decode().map_err(image_error_response)?;
+        println!("Processing {}x{} image with model: {}, confidence: {}", image.width(), image.height(), task_model, params.confidence_threshold);
+        
+        detector
+            .detect(&image, &params)
+            .map(|detections| postprocess::apply(detections, &options))
+            .map_err(|e| failed(&task_model, e))
+    })
+        .await
+        .unwrap_or_else(|e| Err(failed(&model_name, e.to_string())))
+}
+
+fn detection_error(status: StatusCode, message: String) -> (StatusCode, Json<DetectionResponse>) {
+    (
+        status,
+        Json(DetectionResponse {
+            success: false,
+            message,
+            request_id: None,
+            detections: None,
+            events: None,
+            counting_events: None,
+            processing_time: None,
+        })
+    )
+}
+
+// Réponse d'erreur pour une image illisible
+fn image_error_response(e: ImageInputError) -> (StatusCode, Json<DetectionResponse>) {
+    eprintln!("Error decoding image: {}", e);
+    detection_error(e.status_code(), e.to_string())
+}
+
+// Image reçue par l'une des routes de détection
+enum ImageSource {
+    Base64(String),
+    Bytes(Vec<u8>),
+}
+
+impl ImageSource {
+    // Résumé stocké dans detection_requests.image_data (l'image elle-même n'est pas conservée)
+    fn summary(&self) -> String {
+        match self {
+            ImageSource::Base64(data) => format!("base64 ({} chars)", data.len()),
+            ImageSource::Bytes(bytes) => format!("multipart ({} bytes)", bytes.len()),
+        }
+    }
     
-    // Validation des données d'entrée
-    if payload.image_data.is_none() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(DetectionResponse {
-                success: false,
-                message: "No image data provided".to_string(),
-                detections: None,
-                processing_time: None,
-            })
-        );
+    fn decode(&self) -> Result<DynamicImage, ImageInputError> {
+        match self {
+            ImageSource::Base64(data) => decode_base64_image(data),
+            ImageSource::Bytes(bytes) => decode_image_bytes(bytes),
+        }
     }
+}
+
+// Paramètres communs aux routes /detect et /detect/upload
+struct DetectionInput {
+    g_id: Option<String>,
+    model_type: Option<String>,
+    params: DetectionParams,
+    postprocess: PostProcessOptions,
+    image: Option<ImageSource>,
+}
+
+// Enregistrer la requête, lancer la détection puis stocker le résultat
+async fn handle_detection(
+    db: &Database,
+    registry: &DetectorRegistry,
+    trackers: &TrackerRegistry,
+    caller: &Caller,
+    input: DetectionInput,
+) -> (StatusCode, Json<DetectionResponse>) {
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let device = caller.device();
+    // Un appareil identifié par sa clé est suivi sous son nom
+    let g_id = input
+        .g_id
+        .clone()
+        .or_else(|| device.map(|device| device.name.clone()))
+        .unwrap_or_else(|| {
+            let model = input.model_type.as_deref().unwrap_or(registry.default_name());
+            format!("{}_{}", model.to_uppercase(), chrono::Utc::now().timestamp())
+        });
+    let summary = input
+        .image
+        .as_ref()
+        .map(ImageSource::summary)
+        .unwrap_or_else(|| "none".to_string());
     
-    // Simulation de détection d'objets
-    let mock_detections = vec![
-        Detection {
-            class: "person".to_string(),
-            confidence: 0.95,
-            bbox: BoundingBox {
-                x: 100.0,
-                y: 150.0,
-                width: 200.0,
-                height: 300.0,
-            },
-        },
-        Detection {
-            class: "car".to_string(),
-            confidence: 0.87,
-            bbox: BoundingBox {
-                x: 300.0,
-                y: 200.0,
-                width: 250.0,
-                height: 180.0,
-            },
-        },
-    ];
+    if let Err(e) = db
+        .insert_detection_request(&g_id, &request_id, &summary, device.map(|device| device.id))
+        .await
+    {
+        eprintln!("Failed to record detection request {}: {}", request_id, e);
+    }
+    
+    // Le suivi n'a de sens que pour une caméra identifiée par son g_id
+    let tracked = input.g_id.is_some() || device.is_some();
+    let (status, Json(mut response)) = process_detection(registry, input).await;
+    
+    if let (true, Some(detections)) = (tracked, response.detections.as_mut()) {
+        let rules = db.list_counting_rules(Some(&g_id)).await.unwrap_or_else(|e| {
+            eprintln!("Failed to load counting rules for {}: {}", g_id, e);
+            Vec::new()
+        });
+        let (events, counting_events) = trackers.update(&g_id, detections, &rules);
+        if let Err(e) = db.insert_counting_events(&g_id, &counting_events).await {
+            eprintln!("Failed to record counting events {}: {}", request_id, e);
+        }
+        response.events = Some(events);
+        if !rules.is_empty() {
+            response.counting_events = Some(counting_events);
+        }
+    }
+    
+    if let Err(e) = record_detection_result(db, &g_id, &request_id, &response).await {
+        eprintln!("Failed to record detection result {}: {}", request_id, e);
+    }
+    
+    response.request_id = Some(request_id);
+    (status, Json(response))
+}
+
+// Passer la requête à done/failed et stocker les objets détectés
+async fn record_detection_result(
+    db: &Database,
+    g_id: &str,
+    request_id: &str,
+    response: &DetectionResponse,
+) -> Result<(), sqlx::Error> {
+    let detections = response.detections.as_deref().unwrap_or_default();
+    if response.success && !detections.is_empty() {
+        let objects: Vec<DetectedObject> = detections.iter().map(DetectedObject::from).collect();
+        db.insert_detection(request_id, g_id, &objects).await?;
+    }
     
+    let status = if response.success { "done" } else { "failed" };
+    db.update_request_status(request_id, status).await
+}
+
+// Sélection du modèle puis décodage de l'image et détection
+async fn process_detection(
+    registry: &DetectorRegistry,
+    input: DetectionInput,
+) -> (StatusCode, Json<DetectionResponse>) {
+    let start_time = std::time::Instant::now();
+    
+    let detector = match select_detector(registry, input.model_type.as_deref()) {
+        Ok(detector) => detector,
+        Err(response) => return response,
+    };
+    
+    // Validation des données d'entrée
+    let Some(source) = input.image else {
+        return detection_error(StatusCode::BAD_REQUEST, "No image data provided".to_string());
+    };
+    
+    let model_name = detector.name().to_string();
+    let detections = match run_detection(detector, source, input.params, input.postprocess).await {
+        Ok(detections) => detections,
+        Err(response) => return response,
+    };
     let processing_time = start_time.elapsed().as_secs_f32();
     
     let response = DetectionResponse {
         success: true,
-        message: "Detection completed successfully".to_string(),
-        detections: Some(mock_detections),
+        message: format!("Detection completed successfully with {} model", model_name),
+        request_id: None,
+        detections: Some(detections),
+        events: None,
+        counting_events: None,
         processing_time: Some(processing_time),
     };
     
     (StatusCode::OK, Json(response))
 }
 
+// Handler principal pour la détection d'objets (avec JSON)
+async fn detect_objects_json(
+    State(db): State<Database>,
+    State(registry): State<DetectorRegistry>,
+    State(trackers): State<TrackerRegistry>,
+    caller: Caller,
+    Json(payload): Json<DetectionRequest>
+) -> impl IntoResponse {
+    if let Some(image_data) = &payload.image_data {
+        println!("Received detection request: {} base64 chars", image_data.len());
+    }
+    
+    let input = DetectionInput {
+        g_id: payload.g_id,
+        model_type: payload.model_type,
+        params: DetectionParams {
+            confidence_threshold: payload.confidence.unwrap_or(0.5),
+        },
+        postprocess: payload.postprocess,
+        image: payload.image_data.map(ImageSource::Base64),
+    };
+    
+    handle_detection(&db, &registry, &trackers, &caller, input).await
+}
+
 // Handler pour la détection avec upload de fichier
 async fn detect_objects_upload(
+    State(db): State<Database>,
+    State(registry): State<DetectorRegistry>,
+    State(trackers): State<TrackerRegistry>,
+    caller: Caller,
     mut multipart: Multipart
 ) -> impl IntoResponse {
     println!("Received file upload request");
     
-    let start_time = std::time::Instant::now();
     let mut image_data: Option<Vec<u8>> = None;
-    let mut model_type = "default".to_string();
+    let mut g_id: Option<String> = None;
+    let mut model_type: Option<String> = None;
     let mut confidence_threshold = 0.5f32;
+    let mut postprocess = PostProcessOptions::default();
     
     // Traitement des champs multipart
     while let Some(field) = multipart.next_field().await.unwrap_or(None) {
@@ -142,21 +393,18 @@ async fn detect_objects_upload(
                     }
                     Err(e) => {
                         eprintln!("Error reading image data: {}", e);
-                        return (
-                            StatusCode::BAD_REQUEST,
-                            Json(DetectionResponse {
-                                success: false,
-                                message: format!("Error reading image: {}", e),
-                                detections: None,
-                                processing_time: None,
-                            })
-                        );
+                        return detection_error(StatusCode::BAD_REQUEST, format!("Error reading image: {}", e));
                     }
                 }
             }
+            "g_id" => {
+                if let Ok(text) = field.text().await {
+                    g_id = Some(text);
+                }
+            }
             "model_type" => {
                 if let Ok(text) = field.text().await {
-                    model_type = text;
+                    model_type = Some(text);
                 }
             }
             "confidence" => {
@@ -167,88 +415,34 @@ async fn detect_objects_upload(
                 }
             }
             _ => {
-                println!("Unknown field: {}", name);
+                match field.text().await {
+                    Ok(text) if postprocess.set_form_field(&name, &text) => {}
+                    _ => println!("Unknown field: {}", name),
+                }
             }
         }
     }
     
-    // Validation
-    if image_data.is_none() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(DetectionResponse {
-                success: false,
-                message: "No image file provided".to_string(),
-                detections: None,
-                processing_time: None,
-            })
-        );
-    }
-    
-    // Simulation de traitement de l'image
-    // Ici vous pourriez intégrer votre modèle de détection (YOLO, etc.)
-    println!("Processing image with model: {}, confidence: {}", model_type, confidence_threshold);
-    
-    // Simulation de détections
-    let mock_detections = vec![
-        Detection {
-            class: "person".to_string(),
-            confidence: 0.92,
-            bbox: BoundingBox {
-                x: 120.0,
-                y: 80.0,
-                width: 180.0,
-                height: 350.0,
-            },
+    let input = DetectionInput {
+        g_id,
+        model_type,
+        params: DetectionParams {
+            confidence_threshold,
         },
-        Detection {
-            class: "bicycle".to_string(),
-            confidence: 0.78,
-            bbox: BoundingBox {
-                x: 400.0,
-                y: 250.0,
-                width: 120.0,
-                height: 200.0,
-            },
-        },
-    ];
-    
-    let processing_time = start_time.elapsed().as_secs_f32();
-    
-    let response = DetectionResponse {
-        success: true,
-        message: format!("Image processed successfully with {} model", model_type),
-        detections: Some(mock_detections),
-        processing_time: Some(processing_time),
+        postprocess,
+        image: image_data.map(ImageSource::Bytes),
     };
     
-    (StatusCode::OK, Json(response))
+    handle_detection(&db, &registry, &trackers, &caller, input).await
 }
 
 // Handler pour lister les modèles disponibles
-async fn list_models() -> impl IntoResponse {
-    let models = vec![
-        HashMap::from([
-            ("name", "yolov8n"),
-            ("description", "YOLOv8 Nano - Fast and lightweight"),
-            ("size", "6MB"),
-        ]),
-        HashMap::from([
-            ("name", "yolov8s"),
-            ("description", "YOLOv8 Small - Good balance of speed and accuracy"),
-            ("size", "22MB"),
-        ]),
-        HashMap::from([
-            ("name", "yolov8m"),
-            ("description", "YOLOv8 Medium - Higher accuracy"),
-            ("size", "52MB"),
-        ]),
-    ];
-    
-    Json(HashMap::from([
-        ("success", serde_json::Value::Bool(true)),
-        ("models", serde_json::to_value(models).unwrap()),
-    ]))
+async fn list_models(State(registry): State<DetectorRegistry>, _: Authorized<Viewer>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "success": true,
+        "default": registry.default_name(),
+        "models": registry.list(),
+    }))
 }
 
 // Configuration CORS
@@ -267,17 +461,85 @@ async fn main() {
     
     println!("🚀 Starting Detection API Server...");
     
+    // Clés de signature des tokens
+    let keys = match jwt_keys::KeyRing::from_env() {
+        Ok(keys) => keys,
+        Err(e) => {
+            eprintln!("❌ Failed to load JWT keys: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if keys.is_insecure() {
+        if std::env::var("JWT_ALLOW_DEV_SECRET").as_deref() != Ok("1") {
+            eprintln!("❌ Refusing to start with the example JWT secret in the key ring: set JWT_SECRET, JWT_SECRET_FILE or JWT_KEYS_FILE (or JWT_ALLOW_DEV_SECRET=1 in development)");
+            std::process::exit(1);
+        }
+        println!("⚠️ Secret JWT d'exemple utilisé (JWT_ALLOW_DEV_SECRET=1), à ne pas utiliser en production");
+    }
+    for key in keys.summary() {
+        println!("🔑 Clé JWT {} ({:?}){}", key.kid, key.algorithm, if key.current { ", signature" } else { "" });
+    }
+    jwt_keys::install(keys);
+    
+    // Initialisation de la base de données
+    let pool = match database::create_database().await {
+        Ok(pool) => pool,
+        Err(e) => {
+            eprintln!("❌ Failed to initialize database: {}", e);
+            std::process::exit(1);
+        }
+    };
+    
+    // Fusion des observations répétées
+    let dedup = DedupConfig::from_env();
+    if dedup.window_secs > 0 {
+        println!("🔁 Fusion des observations: fenêtre {}s, IoU ≥ {}", dedup.window_secs, dedup.iou_threshold);
+    }
+    
+    // Chargement des modèles de détection
+    let mut detectors = DetectorRegistry::new();
+    detectors.register(Arc::new(ColorDetector::new()));
+    #[cfg(feature = "onnx")]
+    for detector in onnx_detector::load_models(&onnx_detector::models_dir()) {
+        detectors.register(Arc::new(detector));
+    }
+    
+    let db = Database::new(pool).with_dedup(dedup);
+    
+    // Compte administrateur initial
+    if let Err(e) = users::bootstrap_admin(&db).await {
+        eprintln!("❌ Failed to create admin account: {}", e);
+        std::process::exit(1);
+    }
+    
+    // Surveillance des appareils silencieux
+    let heartbeat = HeartbeatConfig::from_env();
+    println!("💓 Appareils hors ligne après {}s sans nouvelles", heartbeat.offline_after.as_secs());
+    tokio::spawn(devices::watch_heartbeats(db.clone(), heartbeat));
+    
+    let state = AppState {
+        db,
+        detectors,
+        trackers: TrackerRegistry::new(TrackerConfig::from_env()),
+    };
+    
+    let frontend_dir = frontend::frontend_dir();
+    
     // Configuration des routes
     let app = Router::new()
-        .route("/", get(root))
+        .route("/status", get(root))
         .route("/health", get(health_check))
         .route("/detect", post(detect_objects_json))
         .route("/detect/upload", post(detect_objects_upload))
+        .route_layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
         .route("/models", get(list_models))
+        .nest("/api", api::router())
+        .merge(frontend::router(frontend_dir.clone()).layer(middleware::from_fn(json_root)))
         .layer(
             ServiceBuilder::new()
                 .layer(cors_layer())
-        );
+        )
+        .with_state(state);
     
     // Configuration du serveur
     let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
@@ -286,11 +548,14 @@ async fn main() {
         
     println!("✅ Server running on http://127.0.0.1:3000");
     println!("📖 Available endpoints:");
-    println!("  GET  /           - API status");
+    println!("  GET  /           - Dashboard ({}), API status with Accept: application/json", frontend_dir.display());
+    println!("  GET  /status     - API status");
     println!("  GET  /health     - Health check");
     println!("  POST /detect     - Object detection (JSON)");
     println!("  POST /detect/upload - Object detection (File upload)");
     println!("  GET  /models     - List available models");
+    println!("  POST /api/login  - Authentication");
+    println!("  *    /api/...    - Detections, history, stats");
     
     // Démarrage du serveur
     axum::serve(listener, app)
@@ -0,0 +1,494 @@
+use sqlx::sqlite::SqlitePool;
+use sqlx::Row;
+use std::fmt;
+
+// Migration du schéma, appliquée une seule fois dans l'ordre des versions
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+// Migrations embarquées ; ne jamais modifier une migration déjà publiée, en ajouter une nouvelle
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "detection_requests et detections",
+        // IF NOT EXISTS : les bases créées avant les migrations sont reprises telles quelles
+        sql: r#"
+        CREATE TABLE IF NOT EXISTS detection_requests (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            g_id TEXT NOT NULL,
+            request_id TEXT NOT NULL UNIQUE,
+            image_data TEXT NOT NULL,
+            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+            status TEXT DEFAULT 'pending'
+        );
+
+        CREATE TABLE IF NOT EXISTS detections (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            request_id TEXT NOT NULL,
+            g_id TEXT NOT NULL,
+            detected_objects TEXT NOT NULL,
+            confidence_scores TEXT NOT NULL,
+            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (request_id) REFERENCES detection_requests (request_id)
+        );
+        "#,
+    },
+    Migration {
+        version: 2,
+        description: "detected_objects normalisés",
+        // Reprise des colonnes JSON existantes puis suppression de celles-ci
+        sql: r#"
+        CREATE TABLE detected_objects (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            request_id TEXT NOT NULL,
+            class TEXT NOT NULL,
+            color TEXT,
+            confidence REAL NOT NULL,
+            bbox_x REAL,
+            bbox_y REAL,
+            bbox_width REAL,
+            bbox_height REAL,
+            FOREIGN KEY (request_id) REFERENCES detection_requests (request_id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX idx_detected_objects_request ON detected_objects (request_id);
+        CREATE INDEX idx_detected_objects_class ON detected_objects (class, confidence);
+
+        INSERT INTO detected_objects (request_id, class, color, confidence, bbox_x, bbox_y, bbox_width, bbox_height)
+        SELECT d.request_id,
+               COALESCE(json_extract(o.value, '$.class'), ''),
+               json_extract(o.value, '$.color'),
+               COALESCE(json_extract(o.value, '$.confidence'),
+                        json_extract(d.confidence_scores, '$[' || o.key || ']'),
+                        0),
+               json_extract(o.value, '$.bbox.x'),
+               json_extract(o.value, '$.bbox.y'),
+               json_extract(o.value, '$.bbox.width'),
+               json_extract(o.value, '$.bbox.height')
+        FROM detections d, json_each(d.detected_objects) o
+        WHERE json_valid(d.detected_objects);
+
+        ALTER TABLE detections DROP COLUMN detected_objects;
+        ALTER TABLE detections DROP COLUMN confidence_scores;
+        "#,
+    },
+    Migration {
+        version: 3,
+        description: "index de l'historique",
+        sql: r#"
+        CREATE INDEX idx_detections_timestamp ON detections (timestamp, id);
+        CREATE INDEX idx_detections_g_id ON detections (g_id, timestamp);
+        CREATE INDEX idx_detected_objects_color ON detected_objects (color, confidence);
+        "#,
+    },
+    Migration {
+        version: 4,
+        description: "daily_stats maintenue par triggers",
+        // Un objet détecté compte pour une unité de cadence le jour de sa détection
+        sql: r#"
+        CREATE TABLE daily_stats (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            day TEXT NOT NULL,
+            g_id TEXT NOT NULL,
+            type TEXT NOT NULL,
+            color TEXT NOT NULL DEFAULT '',
+            cadence INTEGER NOT NULL DEFAULT 0,
+            UNIQUE (day, g_id, type, color)
+        );
+
+        INSERT INTO daily_stats (day, g_id, type, color, cadence)
+        SELECT DATE(d.timestamp), d.g_id, o.class, COALESCE(o.color, ''), COUNT(*)
+        FROM detected_objects o
+        JOIN detections d ON d.request_id = o.request_id
+        GROUP BY 1, 2, 3, 4;
+
+        CREATE TRIGGER trg_daily_stats_insert AFTER INSERT ON detected_objects
+        BEGIN
+            INSERT INTO daily_stats (day, g_id, type, color, cadence)
+            SELECT DATE(d.timestamp), d.g_id, NEW.class, COALESCE(NEW.color, ''), 1
+            FROM detections d
+            WHERE d.request_id = NEW.request_id
+            ON CONFLICT (day, g_id, type, color) DO UPDATE SET cadence = cadence + 1;
+        END;
+
+        CREATE TRIGGER trg_daily_stats_delete AFTER DELETE ON detected_objects
+        BEGIN
+            UPDATE daily_stats SET cadence = cadence - 1
+            WHERE (day, g_id) = (SELECT DATE(d.timestamp), d.g_id FROM detections d WHERE d.request_id = OLD.request_id)
+              AND type = OLD.class
+              AND color = COALESCE(OLD.color, '');
+
+            DELETE FROM daily_stats WHERE cadence <= 0;
+        END;
+        "#,
+    },
+    Migration {
+        version: 5,
+        description: "ref_count et fenêtre d'observation des objets",
+        sql: r#"
+        ALTER TABLE detected_objects ADD COLUMN ref_count INTEGER NOT NULL DEFAULT 1;
+        ALTER TABLE detected_objects ADD COLUMN first_seen DATETIME;
+        ALTER TABLE detected_objects ADD COLUMN last_seen DATETIME;
+
+        UPDATE detected_objects
+        SET first_seen = (SELECT d.timestamp FROM detections d WHERE d.request_id = detected_objects.request_id),
+            last_seen = (SELECT d.timestamp FROM detections d WHERE d.request_id = detected_objects.request_id);
+
+        CREATE INDEX idx_detected_objects_last_seen ON detected_objects (class, last_seen);
+        "#,
+    },
+    Migration {
+        version: 6,
+        description: "track_id des objets suivis",
+        sql: r#"
+        ALTER TABLE detected_objects ADD COLUMN track_id INTEGER;
+        "#,
+    },
+    Migration {
+        version: 7,
+        description: "règles de comptage et counting_events",
+        // daily_stats est reconstruite pour distinguer les détections brutes (rule = '') des événements
+        sql: r#"
+        CREATE TABLE counting_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            g_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            kind TEXT NOT NULL CHECK (kind IN ('zone', 'line')),
+            points TEXT NOT NULL,
+            direction TEXT NOT NULL DEFAULT 'both',
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (g_id, name)
+        );
+
+        CREATE TABLE counting_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            rule_id INTEGER,
+            rule TEXT NOT NULL,
+            g_id TEXT NOT NULL,
+            track_id INTEGER NOT NULL,
+            class TEXT NOT NULL,
+            color TEXT,
+            event TEXT NOT NULL,
+            timestamp DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (rule_id) REFERENCES counting_rules (id) ON DELETE SET NULL
+        );
+
+        CREATE INDEX idx_counting_events_g_id ON counting_events (g_id, timestamp);
+
+        DROP TRIGGER trg_daily_stats_insert;
+        DROP TRIGGER trg_daily_stats_delete;
+
+        CREATE TABLE daily_stats_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            day TEXT NOT NULL,
+            g_id TEXT NOT NULL,
+            type TEXT NOT NULL,
+            color TEXT NOT NULL DEFAULT '',
+            rule TEXT NOT NULL DEFAULT '',
+            event TEXT NOT NULL DEFAULT '',
+            cadence INTEGER NOT NULL DEFAULT 0,
+            UNIQUE (day, g_id, type, color, rule, event)
+        );
+
+        INSERT INTO daily_stats_new (day, g_id, type, color, cadence)
+        SELECT day, g_id, type, color, cadence FROM daily_stats;
+
+        DROP TABLE daily_stats;
+        ALTER TABLE daily_stats_new RENAME TO daily_stats;
+
+        CREATE TRIGGER trg_daily_stats_insert AFTER INSERT ON detected_objects
+        BEGIN
+            INSERT INTO daily_stats (day, g_id, type, color, cadence)
+            SELECT DATE(d.timestamp), d.g_id, NEW.class, COALESCE(NEW.color, ''), 1
+            FROM detections d
+            WHERE d.request_id = NEW.request_id
+            ON CONFLICT (day, g_id, type, color, rule, event) DO UPDATE SET cadence = cadence + 1;
+        END;
+
+        CREATE TRIGGER trg_daily_stats_delete AFTER DELETE ON detected_objects
+        BEGIN
+            UPDATE daily_stats SET cadence = cadence - 1
+            WHERE (day, g_id) = (SELECT DATE(d.timestamp), d.g_id FROM detections d WHERE d.request_id = OLD.request_id)
+              AND type = OLD.class
+              AND color = COALESCE(OLD.color, '')
+              AND rule = ''
+              AND event = '';
+
+            DELETE FROM daily_stats WHERE cadence <= 0;
+        END;
+
+        CREATE TRIGGER trg_daily_stats_event_insert AFTER INSERT ON counting_events
+        BEGIN
+            INSERT INTO daily_stats (day, g_id, type, color, rule, event, cadence)
+            VALUES (DATE(NEW.timestamp), NEW.g_id, NEW.class, COALESCE(NEW.color, ''), NEW.rule, NEW.event, 1)
+            ON CONFLICT (day, g_id, type, color, rule, event) DO UPDATE SET cadence = cadence + 1;
+        END;
+
+        CREATE TRIGGER trg_daily_stats_event_delete AFTER DELETE ON counting_events
+        BEGIN
+            UPDATE daily_stats SET cadence = cadence - 1
+            WHERE day = DATE(OLD.timestamp)
+              AND g_id = OLD.g_id
+              AND type = OLD.class
+              AND color = COALESCE(OLD.color, '')
+              AND rule = OLD.rule
+              AND event = OLD.event;
+
+            DELETE FROM daily_stats WHERE cadence <= 0;
+        END;
+        "#,
+    },
+    Migration {
+        version: 8,
+        description: "devices et attribution des détections",
+        sql: r#"
+        CREATE TABLE devices (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            location TEXT,
+            type TEXT NOT NULL DEFAULT 'camera',
+            enabled BOOLEAN NOT NULL DEFAULT 1,
+            api_key_hash TEXT NOT NULL UNIQUE,
+            key_prefix TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+
+        ALTER TABLE detection_requests ADD COLUMN device_id INTEGER REFERENCES devices (id) ON DELETE SET NULL;
+        CREATE INDEX idx_detection_requests_device ON detection_requests (device_id);
+        "#,
+    },
+    Migration {
+        version: 9,
+        description: "statut des appareils et historique",
+        sql: r#"
+        ALTER TABLE devices ADD COLUMN status TEXT NOT NULL DEFAULT 'unknown';
+        ALTER TABLE devices ADD COLUMN last_seen_at DATETIME;
+
+        CREATE TABLE device_status_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            device_id INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            changed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (device_id) REFERENCES devices (id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX idx_device_status_history_device ON device_status_history (device_id, changed_at);
+        "#,
+    },
+    Migration {
+        version: 10,
+        description: "comptes utilisateurs",
+        sql: r#"
+        CREATE TABLE users (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            username TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            role TEXT NOT NULL DEFAULT 'user',
+            enabled BOOLEAN NOT NULL DEFAULT 1,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        );
+        "#,
+    },
+    Migration {
+        version: 11,
+        description: "sessions et jetons de rafraîchissement",
+        sql: r#"
+        CREATE TABLE sessions (
+            id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            user_agent TEXT,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            last_used_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            expires_at DATETIME NOT NULL,
+            revoked_at DATETIME,
+            revoked_reason TEXT,
+            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX idx_sessions_user ON sessions (user_id);
+
+        CREATE TABLE refresh_tokens (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL,
+            token_hash TEXT NOT NULL UNIQUE,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            expires_at DATETIME NOT NULL,
+            used_at DATETIME,
+            FOREIGN KEY (session_id) REFERENCES sessions (id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX idx_refresh_tokens_session ON refresh_tokens (session_id);
+        "#,
+    },
+];
+
+// Dernière version connue de ce binaire
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Database(sqlx::Error),
+    // La base a été migrée par une version plus récente du serveur
+    NewerSchema { found: i64, supported: i64 },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Database(e) => write!(f, "Database error: {}", e),
+            MigrationError::NewerSchema { found, supported } => write!(
+                f,
+                "Database schema version {} is newer than the supported version {}, please upgrade the server",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(e: sqlx::Error) -> Self {
+        MigrationError::Database(e)
+    }
+}
+
+// Version actuelle du schéma (0 pour une base vide)
+pub async fn current_version(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query("SELECT COALESCE(MAX(version), 0) as version FROM schema_version")
+        .fetch_one(pool)
+        .await?;
+
+    Ok(row.get::<i64, _>("version"))
+}
+
+// Appliquer les migrations manquantes, chacune dans sa propre transaction
+pub async fn run(pool: &SqlitePool) -> Result<(), MigrationError> {
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    let current = current_version(pool).await?;
+    let supported = latest_version();
+    if current > supported {
+        return Err(MigrationError::NewerSchema {
+            found: current,
+            supported,
+        });
+    }
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > current) {
+        let mut tx = pool.begin().await?;
+        sqlx::raw_sql(migration.sql).execute(&mut *tx).await?;
+        sqlx::query("INSERT INTO schema_version (version, description) VALUES (?, ?)")
+            .bind(migration.version)
+            .bind(migration.description)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        println!("🗄️ Migration {} appliquée: {}", migration.version, migration.description);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    // Une seule connexion : chaque connexion :memory: a sa propre base
+    async fn memory_pool() -> SqlitePool {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_versions_are_strictly_increasing() {
+        assert!(MIGRATIONS.windows(2).all(|pair| pair[0].version < pair[1].version));
+        assert!(MIGRATIONS.first().is_some_and(|migration| migration.version == 1));
+    }
+
+    #[tokio::test]
+    async fn test_run_is_idempotent() {
+        let pool = memory_pool().await;
+        run(&pool).await.unwrap();
+        run(&pool).await.unwrap();
+
+        assert_eq!(current_version(&pool).await.unwrap(), latest_version());
+        let applied = sqlx::query("SELECT COUNT(*) as count FROM schema_version")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(applied.get::<i64, _>("count"), MIGRATIONS.len() as i64);
+    }
+
+    #[tokio::test]
+    async fn test_moves_json_objects_to_detected_objects() {
+        let pool = memory_pool().await;
+        sqlx::raw_sql(MIGRATIONS[0].sql).execute(&pool).await.unwrap();
+        sqlx::raw_sql(
+            r#"
+            INSERT INTO detection_requests (g_id, request_id, image_data) VALUES ('CAM_1', 'r1', '');
+            INSERT INTO detections (request_id, g_id, detected_objects, confidence_scores)
+            VALUES ('r1', 'CAM_1', '[{"class":"STM32","color":"blue"},{"class":"Carte microchip"}]', '[0.9,0.4]');
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("CREATE TABLE schema_version (version INTEGER PRIMARY KEY, description TEXT NOT NULL, applied_at DATETIME)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO schema_version (version, description) VALUES (1, 'v1')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        run(&pool).await.unwrap();
+
+        let rows = sqlx::query("SELECT class, color, confidence FROM detected_objects ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get::<String, _>("class"), "STM32");
+        assert_eq!(rows[0].get::<Option<String>, _>("color").as_deref(), Some("blue"));
+        assert!((rows[1].get::<f64, _>("confidence") - 0.4).abs() < 1e-9);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_newer_schema() {
+        let pool = memory_pool().await;
+        run(&pool).await.unwrap();
+        sqlx::query("INSERT INTO schema_version (version, description) VALUES (?, 'future')")
+            .bind(latest_version() + 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            run(&pool).await,
+            Err(MigrationError::NewerSchema { .. })
+        ));
+    }
+}
@@ -0,0 +1,325 @@
+use image::{imageops::FilterType, DynamicImage, Rgb, RgbImage};
+use std::path::{Path, PathBuf};
+use tract_onnx::prelude::*;
+
+use crate::detector::{BoundingBox, Detection, DetectionParams, Detector, DetectorInfo};
+
+// Dossier des modèles par défaut (lancement depuis backend/)
+const DEFAULT_MODELS_DIR: &str = "models";
+// Taille d'entrée utilisée si le modèle a des dimensions dynamiques
+const DEFAULT_INPUT_SIZE: usize = 640;
+// Couleur de remplissage du letterbox (comme Ultralytics)
+const LETTERBOX_FILL: u8 = 114;
+
+type OnnxPlan = TypedSimplePlan<TypedModel>;
+
+// Chemin des modèles ONNX, surchargeable via MODELS_DIR
+pub fn models_dir() -> PathBuf {
+    std::env::var("MODELS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_MODELS_DIR))
+}
+
+// Charger tous les fichiers .onnx du dossier (les modèles invalides sont ignorés)
+pub fn load_models(dir: &Path) -> Vec<OnnxDetector> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        println!("ℹ️ Aucun dossier de modèles ONNX: {}", dir.display());
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "onnx"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| match OnnxDetector::load(&path) {
+            Ok(detector) => Some(detector),
+            Err(e) => {
+                eprintln!("❌ Impossible de charger {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Détecteur YOLO (v5 ou v8) exécuté sur CPU avec tract
+pub struct OnnxDetector {
+    name: String,
+    plan: OnnxPlan,
+    input_width: usize,
+    input_height: usize,
+    labels: Vec<String>,
+}
+
+impl OnnxDetector {
+    // Les noms de classes sont lus dans un fichier voisin <modèle>.names (une classe par ligne)
+    pub fn load(path: &Path) -> TractResult<Self> {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "onnx".to_string());
+
+        let model = tract_onnx::onnx().model_for_path(path)?;
+        let (input_height, input_width) = model
+            .input_fact(0)?
+            .shape
+            .as_concrete_finite()?
+            .filter(|shape| shape.len() == 4)
+            .map(|shape| (shape[2], shape[3]))
+            .unwrap_or((DEFAULT_INPUT_SIZE, DEFAULT_INPUT_SIZE));
+
+        let plan = model
+            .with_input_fact(0, f32::fact([1, 3, input_height, input_width]).into())?
+            .into_optimized()?
+            .into_runnable()?;
+
+        let labels = std::fs::read_to_string(path.with_extension("names"))
+            .map(|content| {
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Self {
+            name,
+            plan,
+            input_width,
+            input_height,
+            labels,
+        })
+    }
+
+    fn label(&self, class_id: usize) -> String {
+        self.labels
+            .get(class_id)
+            .cloned()
+            .unwrap_or_else(|| format!("class_{}", class_id))
+    }
+}
+
+impl Detector for OnnxDetector {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn describe(&self) -> DetectorInfo {
+        DetectorInfo {
+            name: self.name.clone(),
+            description: format!(
+                "YOLO ONNX (CPU), entrée {}x{}",
+                self.input_width, self.input_height
+            ),
+            classes: self.labels.clone(),
+        }
+    }
+
+    fn detect(&self, image: &DynamicImage, params: &DetectionParams) -> Result<Vec<Detection>, String> {
+        let letterbox = Letterbox::new(image, self.input_width, self.input_height);
+
+        let input: Tensor = tract_ndarray::Array4::from_shape_fn(
+            (1, 3, self.input_height, self.input_width),
+            |(_, c, y, x)| letterbox.image.get_pixel(x as u32, y as u32)[c] as f32 / 255.0,
+        )
+        .into();
+
+        let outputs = self
+            .plan
+            .run(tvec!(input.into()))
+            .map_err(|e| e.to_string())?;
+        let output = outputs[0]
+            .to_array_view::<f32>()
+            .map_err(|e| e.to_string())?;
+        let shape = output.shape();
+        if shape.len() != 3 {
+            return Err(format!("Unexpected YOLO output shape: {:?}", shape));
+        }
+
+        let (rows, cols) = (shape[1], shape[2]);
+        let data: Vec<f32> = output.iter().copied().collect();
+        let candidates = decode_yolo_output(&data, rows, cols, self.labels.len(), params.confidence_threshold);
+
+        // Les boîtes redondantes sont supprimées par la NMS du post-traitement
+        let detections = candidates
+            .into_iter()
+            .map(|candidate| Detection {
+                class: self.label(candidate.class_id),
+                color: None,
+                confidence: candidate.score,
+                bbox: letterbox.to_original(candidate.bbox),
+                track_id: None,
+            })
+            .collect();
+
+        Ok(detections)
+    }
+}
+
+// Image redimensionnée en conservant le ratio, centrée sur fond gris
+struct Letterbox {
+    image: RgbImage,
+    scale: f32,
+    pad_x: f32,
+    pad_y: f32,
+    orig_width: f32,
+    orig_height: f32,
+}
+
+impl Letterbox {
+    fn new(image: &DynamicImage, width: usize, height: usize) -> Self {
+        let (orig_width, orig_height) = (image.width() as f32, image.height() as f32);
+        let scale = (width as f32 / orig_width).min(height as f32 / orig_height);
+        let resized_width = ((orig_width * scale).round() as u32).max(1);
+        let resized_height = ((orig_height * scale).round() as u32).max(1);
+        let pad_x = (width as u32 - resized_width) / 2;
+        let pad_y = (height as u32 - resized_height) / 2;
+
+        let resized = image
+            .resize_exact(resized_width, resized_height, FilterType::Triangle)
+            .to_rgb8();
+        let mut canvas = RgbImage::from_pixel(width as u32, height as u32, Rgb([LETTERBOX_FILL; 3]));
+        image::imageops::overlay(&mut canvas, &resized, pad_x as i64, pad_y as i64);
+
+        Self {
+            image: canvas,
+            scale,
+            pad_x: pad_x as f32,
+            pad_y: pad_y as f32,
+            orig_width,
+            orig_height,
+        }
+    }
+
+    // Ramener une boîte [x1, y1, x2, y2] du letterbox vers l'image d'origine
+    fn to_original(&self, [x1, y1, x2, y2]: [f32; 4]) -> BoundingBox {
+        let x1 = ((x1 - self.pad_x) / self.scale).clamp(0.0, self.orig_width);
+        let y1 = ((y1 - self.pad_y) / self.scale).clamp(0.0, self.orig_height);
+        let x2 = ((x2 - self.pad_x) / self.scale).clamp(0.0, self.orig_width);
+        let y2 = ((y2 - self.pad_y) / self.scale).clamp(0.0, self.orig_height);
+
+        BoundingBox {
+            x: x1,
+            y: y1,
+            width: x2 - x1,
+            height: y2 - y1,
+        }
+    }
+}
+
+// Boîte candidate en coordonnées du letterbox [x1, y1, x2, y2]
+#[derive(Debug)]
+struct Candidate {
+    class_id: usize,
+    score: f32,
+    bbox: [f32; 4],
+}
+
+// Décoder la sortie YOLO :
+// - v8 : [1, 4 + nc, N] (cx, cy, w, h, scores par classe)
+// - v5 : [1, N, 5 + nc] (cx, cy, w, h, objectness, scores par classe)
+fn decode_yolo_output(data: &[f32], rows: usize, cols: usize, num_labels: usize, threshold: f32) -> Vec<Candidate> {
+    // Sans fichier de classes, la plus petite dimension est celle des attributs
+    let channels_first = if num_labels > 0 {
+        rows == num_labels + 4 || rows == num_labels + 5
+    } else {
+        rows < cols
+    };
+    let (num_boxes, num_attrs) = if channels_first { (cols, rows) } else { (rows, cols) };
+    let is_v5 = if num_labels > 0 {
+        num_attrs == num_labels + 5
+    } else {
+        !channels_first
+    };
+    let attr = |i: usize, a: usize| {
+        if channels_first {
+            data[a * cols + i]
+        } else {
+            data[i * cols + a]
+        }
+    };
+
+    let first_class = if is_v5 { 5 } else { 4 };
+    if num_attrs <= first_class {
+        return Vec::new();
+    }
+
+    let mut candidates = Vec::new();
+    for i in 0..num_boxes {
+        let objectness = if is_v5 { attr(i, 4) } else { 1.0 };
+        let (class_id, class_score) = (first_class..num_attrs)
+            .map(|a| (a - first_class, attr(i, a)))
+            .fold((0, f32::MIN), |best, current| if current.1 > best.1 { current } else { best });
+
+        let score = objectness * class_score;
+        if score < threshold {
+            continue;
+        }
+
+        let (cx, cy, w, h) = (attr(i, 0), attr(i, 1), attr(i, 2), attr(i, 3));
+        candidates.push(Candidate {
+            class_id,
+            score,
+            bbox: [cx - w / 2.0, cy - h / 2.0, cx + w / 2.0, cy + h / 2.0],
+        });
+    }
+
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_letterbox_maps_boxes_back() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(1280, 720));
+        let letterbox = Letterbox::new(&image, 640, 640);
+        assert_eq!(letterbox.scale, 0.5);
+        assert_eq!((letterbox.pad_x, letterbox.pad_y), (0.0, 140.0));
+
+        let bbox = letterbox.to_original([100.0, 240.0, 200.0, 340.0]);
+        assert_eq!((bbox.x, bbox.y, bbox.width, bbox.height), (200.0, 200.0, 200.0, 200.0));
+    }
+
+    #[test]
+    fn test_decodes_yolov8_layout() {
+        // 2 classes, 3 boîtes : [cx, cy, w, h, score0, score1] en colonnes
+        let data = vec![
+            100.0, 300.0, 50.0, // cx
+            100.0, 300.0, 50.0, // cy
+            20.0, 40.0, 10.0, // w
+            20.0, 40.0, 10.0, // h
+            0.9, 0.1, 0.2, // classe 0
+            0.05, 0.8, 0.1, // classe 1
+        ];
+        let candidates = decode_yolo_output(&data, 6, 3, 2, 0.5);
+        assert_eq!(candidates.len(), 2);
+        assert_eq!(candidates[0].class_id, 0);
+        assert_eq!(candidates[0].bbox, [90.0, 90.0, 110.0, 110.0]);
+        assert_eq!(candidates[1].class_id, 1);
+    }
+
+    #[test]
+    fn test_decodes_yolov5_layout_with_objectness() {
+        let data = vec![
+            50.0, 50.0, 10.0, 10.0, 0.9, 0.9, 0.1, //
+            80.0, 80.0, 10.0, 10.0, 0.4, 0.9, 0.1, //
+            20.0, 20.0, 10.0, 10.0, 0.9, 0.1, 0.1, //
+            30.0, 30.0, 10.0, 10.0, 0.9, 0.2, 0.7, //
+            10.0, 90.0, 10.0, 10.0, 0.1, 0.9, 0.9, //
+            60.0, 10.0, 10.0, 10.0, 0.0, 0.0, 0.0, //
+            70.0, 70.0, 10.0, 10.0, 0.0, 0.0, 0.0, //
+            90.0, 20.0, 10.0, 10.0, 0.0, 0.0, 0.0, //
+        ];
+        let candidates = decode_yolo_output(&data, 8, 7, 2, 0.5);
+        assert_eq!(candidates.len(), 2);
+        assert!((candidates[0].score - 0.81).abs() < 1e-6);
+        assert_eq!(candidates[1].class_id, 1);
+    }
+}
@@ -0,0 +1,214 @@
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::OnceLock;
+
+// Recommandation OWASP pour Argon2id : 19 Mio, 2 passes, 1 voie
+const DEFAULT_MEMORY_KIB: u32 = 19 * 1024;
+const DEFAULT_ITERATIONS: u32 = 2;
+const DEFAULT_PARALLELISM: u32 = 1;
+
+static CONFIG: OnceLock<PasswordConfig> = OnceLock::new();
+
+// Coût du hachage Argon2id (surchargeable via PASSWORD_MEMORY_KIB, PASSWORD_ITERATIONS, PASSWORD_PARALLELISM)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PasswordConfig {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            memory_kib: DEFAULT_MEMORY_KIB,
+            iterations: DEFAULT_ITERATIONS,
+            parallelism: DEFAULT_PARALLELISM,
+        }
+    }
+}
+
+impl PasswordConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let env = |name: &str, default: u32| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        let config = Self {
+            memory_kib: env("PASSWORD_MEMORY_KIB", default.memory_kib),
+            iterations: env("PASSWORD_ITERATIONS", default.iterations),
+            parallelism: env("PASSWORD_PARALLELISM", default.parallelism),
+        };
+
+        match config.params() {
+            Ok(_) => config,
+            Err(e) => {
+                eprintln!("⚠️ Paramètres de hachage invalides ({}), valeurs par défaut utilisées", e);
+                default
+            }
+        }
+    }
+
+    fn params(&self) -> Result<Params, argon2::Error> {
+        Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+    }
+
+    fn hasher(&self) -> Result<Argon2<'static>, argon2::Error> {
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params()?))
+    }
+
+    // Un hash produit avec d'autres paramètres doit être recalculé
+    fn is_current(&self, hash: &PasswordHash) -> bool {
+        let Ok(params) = Params::try_from(hash) else {
+            return false;
+        };
+        hash.algorithm == Algorithm::Argon2id.ident()
+            && hash.version == Some(Version::V0x13.into())
+            && params.m_cost() == self.memory_kib
+            && params.t_cost() == self.iterations
+            && params.p_cost() == self.parallelism
+    }
+}
+
+// Configuration lue une seule fois, au premier hachage
+pub fn config() -> &'static PasswordConfig {
+    CONFIG.get_or_init(PasswordConfig::from_env)
+}
+
+#[derive(Debug)]
+pub enum PasswordError {
+    Hash(argon2::password_hash::Error),
+    // Le calcul en tâche bloquante a échoué
+    Task(tokio::task::JoinError),
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordError::Hash(e) => write!(f, "Password hashing error: {}", e),
+            PasswordError::Task(e) => write!(f, "Password hashing task failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+impl From<argon2::password_hash::Error> for PasswordError {
+    fn from(e: argon2::password_hash::Error) -> Self {
+        PasswordError::Hash(e)
+    }
+}
+
+impl From<argon2::Error> for PasswordError {
+    fn from(e: argon2::Error) -> Self {
+        PasswordError::Hash(e.into())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordCheck {
+    Invalid,
+    Valid,
+    // Mot de passe correct mais hash obsolète (SHA-256 historique ou anciens paramètres)
+    NeedsRehash,
+}
+
+impl PasswordCheck {
+    pub fn is_valid(self) -> bool {
+        self != PasswordCheck::Invalid
+    }
+}
+
+// Hash SHA-256 non salé des premières versions, reconnu uniquement pour la migration
+fn legacy_hash(password: &str) -> String {
+    format!("{:x}", Sha256::digest(password.as_bytes()))
+}
+
+fn hash_with(config: &PasswordConfig, password: &str) -> Result<String, PasswordError> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(config.hasher()?.hash_password(password.as_bytes(), &salt)?.to_string())
+}
+
+fn verify_with(config: &PasswordConfig, password: &str, stored: &str) -> PasswordCheck {
+    if !stored.starts_with('$') {
+        return if legacy_hash(password) == stored {
+            PasswordCheck::NeedsRehash
+        } else {
+            PasswordCheck::Invalid
+        };
+    }
+
+    let Ok(hash) = PasswordHash::new(stored) else {
+        eprintln!("⚠️ Hash de mot de passe illisible");
+        return PasswordCheck::Invalid;
+    };
+    // Les paramètres sont lus dans le hash : un ancien coût reste vérifiable
+    if Argon2::default().verify_password(password.as_bytes(), &hash).is_err() {
+        return PasswordCheck::Invalid;
+    }
+    if config.is_current(&hash) {
+        PasswordCheck::Valid
+    } else {
+        PasswordCheck::NeedsRehash
+    }
+}
+
+// Hash PHC ($argon2id$...) avec sel aléatoire ; calculé hors du runtime async
+pub async fn hash_password(password: &str) -> Result<String, PasswordError> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || hash_with(config(), &password))
+        .await
+        .map_err(PasswordError::Task)?
+}
+
+pub async fn verify_password(password: &str, stored: &str) -> Result<PasswordCheck, PasswordError> {
+    let password = password.to_string();
+    let stored = stored.to_string();
+    tokio::task::spawn_blocking(move || verify_with(config(), &password, &stored))
+        .await
+        .map_err(PasswordError::Task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Coût minimal pour des tests rapides
+    const FAST: PasswordConfig = PasswordConfig {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_hashes_are_salted_phc_strings() {
+        let first = hash_with(&FAST, "password123").unwrap();
+        let second = hash_with(&FAST, "password123").unwrap();
+        assert!(first.starts_with("$argon2id$v=19$m=64,t=1,p=1$"));
+        assert_ne!(first, second);
+
+        assert_eq!(verify_with(&FAST, "password123", &first), PasswordCheck::Valid);
+        assert_eq!(verify_with(&FAST, "wrong_pass", &first), PasswordCheck::Invalid);
+    }
+
+    #[test]
+    fn test_flags_outdated_hashes() {
+        // SHA-256 historique
+        let legacy = legacy_hash("password123");
+        assert_eq!(verify_with(&FAST, "password123", &legacy), PasswordCheck::NeedsRehash);
+        assert_eq!(verify_with(&FAST, "wrong_pass", &legacy), PasswordCheck::Invalid);
+
+        // Coût relevé depuis le hachage
+        let stronger = PasswordConfig {
+            iterations: 2,
+            ..FAST
+        };
+        let hash = hash_with(&FAST, "password123").unwrap();
+        assert_eq!(verify_with(&stronger, "password123", &hash), PasswordCheck::NeedsRehash);
+        assert_eq!(verify_with(&FAST, "password123", "$garbage"), PasswordCheck::Invalid);
+    }
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+
+use crate::detector::{BoundingBox, Detection};
+
+// Seuil IoU par défaut (valeur usuelle des modèles YOLO)
+pub const DEFAULT_NMS_IOU: f32 = 0.45;
+
+// Options de post-traitement communes à /detect et /detect/upload
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PostProcessOptions {
+    // Seuil IoU de la NMS (1.0 désactive la suppression)
+    pub nms_iou: Option<f32>,
+    // NMS toutes classes confondues plutôt que classe par classe
+    pub class_agnostic: Option<bool>,
+    // Nombre maximal de détections renvoyées
+    pub top_k: Option<usize>,
+    // Classes (ou couleurs) autorisées
+    pub classes: Option<Vec<String>>,
+    // Classes (ou couleurs) exclues
+    pub exclude_classes: Option<Vec<String>>,
+}
+
+impl PostProcessOptions {
+    // Lire un champ de formulaire multipart, renvoie false si le champ est inconnu
+    pub fn set_form_field(&mut self, name: &str, value: &str) -> bool {
+        let list = || {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+
+        match name {
+            "nms_iou" => self.nms_iou = value.parse().ok(),
+            "class_agnostic" => self.class_agnostic = value.parse().ok(),
+            "top_k" => self.top_k = value.parse().ok(),
+            "classes" => self.classes = Some(list()),
+            "exclude_classes" => self.exclude_classes = Some(list()),
+            _ => return false,
+        }
+        true
+    }
+}
+
+// Une détection correspond si sa classe ou sa couleur est dans la liste
+fn matches(detection: &Detection, names: &[String]) -> bool {
+    names.iter().any(|name| {
+        name.eq_ignore_ascii_case(&detection.class)
+            || detection
+                .color
+                .as_deref()
+                .is_some_and(|color| name.eq_ignore_ascii_case(color))
+    })
+}
+
+// Filtrer par classe, supprimer les doublons puis limiter le nombre de résultats
+pub fn apply(detections: Vec<Detection>, options: &PostProcessOptions) -> Vec<Detection> {
+    let mut detections: Vec<Detection> = detections
+        .into_iter()
+        .filter(|detection| {
+            options
+                .classes
+                .as_ref()
+                .is_none_or(|allowed| matches(detection, allowed))
+        })
+        .filter(|detection| {
+            options
+                .exclude_classes
+                .as_ref()
+                .is_none_or(|denied| !matches(detection, denied))
+        })
+        .collect();
+
+    let iou_threshold = options.nms_iou.unwrap_or(DEFAULT_NMS_IOU);
+    if iou_threshold < 1.0 {
+        detections = non_max_suppression(detections, iou_threshold, options.class_agnostic.unwrap_or(false));
+    } else {
+        detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    }
+
+    if let Some(top_k) = options.top_k {
+        detections.truncate(top_k);
+    }
+    detections
+}
+
+pub fn iou(a: &BoundingBox, b: &BoundingBox) -> f32 {
+    let width = ((a.x + a.width).min(b.x + b.width) - a.x.max(b.x)).max(0.0);
+    let height = ((a.y + a.height).min(b.y + b.height) - a.y.max(b.y)).max(0.0);
+    let intersection = width * height;
+    let union = a.width * a.height + b.width * b.height - intersection;
+    if union > 0.0 {
+        intersection / union
+    } else {
+        0.0
+    }
+}
+
+// Suppression des non-maxima, résultats triés par confiance décroissante
+pub fn non_max_suppression(mut detections: Vec<Detection>, iou_threshold: f32, class_agnostic: bool) -> Vec<Detection> {
+    detections.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+    let mut kept: Vec<Detection> = Vec::new();
+    for detection in detections {
+        let suppressed = kept.iter().any(|other| {
+            (class_agnostic || other.class == detection.class)
+                && iou(&other.bbox, &detection.bbox) > iou_threshold
+        });
+        if !suppressed {
+            kept.push(detection);
+        }
+    }
+    kept
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(class: &str, color: Option<&str>, confidence: f32, x: f32) -> Detection {
+        Detection {
+            class: class.to_string(),
+            color: color.map(str::to_string),
+            confidence,
+            bbox: BoundingBox {
+                x,
+                y: 0.0,
+                width: 10.0,
+                height: 10.0,
+            },
+            track_id: None,
+        }
+    }
+
+    #[test]
+    fn test_iou() {
+        let a = detection("a", None, 1.0, 0.0).bbox;
+        let b = detection("a", None, 1.0, 5.0).bbox;
+        assert_eq!(iou(&a, &a), 1.0);
+        assert!((iou(&a, &b) - 50.0 / 150.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_nms_class_aware_and_agnostic() {
+        let detections = vec![
+            detection("STM32", None, 0.7, 0.0),
+            detection("STM32", None, 0.9, 1.0),
+            detection("Carte microchip", None, 0.8, 1.0),
+        ];
+
+        let aware = non_max_suppression(detections.clone(), 0.45, false);
+        assert_eq!(aware.len(), 2);
+        assert_eq!(aware[0].confidence, 0.9);
+
+        let agnostic = non_max_suppression(detections, 0.45, true);
+        assert_eq!(agnostic.len(), 1);
+    }
+
+    #[test]
+    fn test_class_filters_and_top_k() {
+        let detections = vec![
+            detection("STM32", Some("blue"), 0.9, 0.0),
+            detection("Carte microchip", Some("red"), 0.8, 50.0),
+            detection("Carte personnalisée", Some("green"), 0.7, 100.0),
+        ];
+
+        let allowed = PostProcessOptions {
+            classes: Some(vec!["red".to_string(), "STM32".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(apply(detections.clone(), &allowed).len(), 2);
+
+        let denied = PostProcessOptions {
+            exclude_classes: Some(vec!["stm32".to_string()]),
+            top_k: Some(1),
+            ..Default::default()
+        };
+        let result = apply(detections, &denied);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].class, "Carte microchip");
+    }
+
+    #[test]
+    fn test_form_fields() {
+        let mut options = PostProcessOptions::default();
+        assert!(options.set_form_field("classes", "red, blue,"));
+        assert!(options.set_form_field("top_k", "3"));
+        assert!(!options.set_form_field("image", ""));
+        assert_eq!(options.classes, Some(vec!["red".to_string(), "blue".to_string()]));
+        assert_eq!(options.top_k, Some(3));
+    }
+}
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+use crate::users::User;
+
+// Jetons d'accès courts, jetons de rafraîchissement longs et à usage unique
+const DEFAULT_ACCESS_MINUTES: i64 = 15;
+const DEFAULT_REFRESH_DAYS: i64 = 30;
+// Préfixe des jetons de rafraîchissement, pour les reconnaître dans les logs
+const REFRESH_TOKEN_PREFIX: &str = "rt_";
+
+static CONFIG: OnceLock<SessionConfig> = OnceLock::new();
+
+// Durées de vie (surchargeables via ACCESS_TOKEN_MINUTES et REFRESH_TOKEN_DAYS)
+#[derive(Debug, Clone, Copy)]
+pub struct SessionConfig {
+    pub access_ttl: chrono::Duration,
+    pub refresh_ttl: chrono::Duration,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            access_ttl: chrono::Duration::minutes(DEFAULT_ACCESS_MINUTES),
+            refresh_ttl: chrono::Duration::days(DEFAULT_REFRESH_DAYS),
+        }
+    }
+}
+
+impl SessionConfig {
+    pub fn from_env() -> Self {
+        let env = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse::<i64>().ok())
+                .filter(|value| *value > 0)
+        };
+        let default = Self::default();
+        Self {
+            access_ttl: env("ACCESS_TOKEN_MINUTES").map_or(default.access_ttl, chrono::Duration::minutes),
+            refresh_ttl: env("REFRESH_TOKEN_DAYS").map_or(default.refresh_ttl, chrono::Duration::days),
+        }
+    }
+}
+
+pub fn config() -> &'static SessionConfig {
+    CONFIG.get_or_init(SessionConfig::from_env)
+}
+
+// Session ouverte par une connexion ; ses jetons de rafraîchissement successifs forment une famille
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub user_id: i64,
+    pub username: String,
+    pub user_agent: Option<String>,
+    pub created_at: String,
+    pub last_used_at: String,
+    pub expires_at: String,
+    // Session du token ayant servi à la requête
+    #[serde(default)]
+    pub current: bool,
+}
+
+// Résultat de l'échange d'un jeton de rafraîchissement
+#[derive(Debug)]
+pub enum RefreshOutcome {
+    // Ancien jeton consommé, nouveau jeton enregistré
+    Rotated { session_id: String, user: User },
+    // Jeton inconnu, expiré, session révoquée ou compte désactivé
+    Invalid,
+    // Jeton déjà utilisé : probablement volé, toute la session est révoquée
+    Reused { session_id: String },
+}
+
+// Motif de révocation, conservé dans sessions.revoked_reason
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RevokeReason {
+    Logout,
+    Revoked,
+    Reuse,
+    AccountChanged,
+    PasswordChanged,
+}
+
+impl RevokeReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RevokeReason::Logout => "logout",
+            RevokeReason::Revoked => "revoked",
+            RevokeReason::Reuse => "reuse",
+            RevokeReason::AccountChanged => "account_changed",
+            RevokeReason::PasswordChanged => "password_changed",
+        }
+    }
+}
+
+pub fn generate_session_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+// Nouveau jeton de rafraîchissement (244 bits aléatoires issus de deux UUID v4)
+pub fn generate_refresh_token() -> String {
+    format!(
+        "{}{}{}",
+        REFRESH_TOKEN_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+// Jetons longs et aléatoires : un SHA-256 suffit pour les stocker
+pub fn hash_refresh_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_tokens_are_unique_and_hashed() {
+        let token = generate_refresh_token();
+        assert!(token.starts_with(REFRESH_TOKEN_PREFIX));
+        assert_ne!(token, generate_refresh_token());
+        assert_eq!(hash_refresh_token(&token), hash_refresh_token(&token));
+        assert_ne!(hash_refresh_token(&token), token);
+    }
+}
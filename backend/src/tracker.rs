@@ -0,0 +1,302 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::counting::{CountingEvent, CountingRule, CountingState};
+use crate::detector::{BoundingBox, Detection};
+use crate::postprocess::iou;
+
+// Un tracker sans nouvelle image depuis ce délai est oublié
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrackerConfig {
+    // IoU minimal entre la position prédite d'une piste et une détection
+    pub iou_threshold: f32,
+    // Nombre d'images consécutives sans détection avant la sortie d'une piste
+    pub max_missed: u32,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            iou_threshold: 0.3,
+            max_missed: 3,
+        }
+    }
+}
+
+impl TrackerConfig {
+    // Surchargeable via TRACK_IOU et TRACK_MAX_MISSED
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let env = |name: &str| std::env::var(name).ok();
+        Self {
+            iou_threshold: env("TRACK_IOU")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default.iou_threshold),
+            max_missed: env("TRACK_MAX_MISSED")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default.max_missed),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TrackEventKind {
+    Enter,
+    Exit,
+}
+
+// Apparition ou disparition d'un objet suivi, renvoyée avec la détection
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrackEvent {
+    pub event: TrackEventKind,
+    pub track_id: u64,
+    pub class: String,
+    // Dernière position connue
+    pub bbox: BoundingBox,
+}
+
+// Objet suivi d'une image à l'autre, avec une vitesse constante estimée
+#[derive(Debug, Clone)]
+struct Track {
+    id: u64,
+    class: String,
+    bbox: BoundingBox,
+    velocity: (f32, f32),
+    missed: u32,
+}
+
+impl Track {
+    // Position attendue sur l'image suivante
+    fn predicted(&self) -> BoundingBox {
+        let steps = (self.missed + 1) as f32;
+        BoundingBox {
+            x: self.bbox.x + self.velocity.0 * steps,
+            y: self.bbox.y + self.velocity.1 * steps,
+            ..self.bbox.clone()
+        }
+    }
+
+    fn update(&mut self, bbox: &BoundingBox) {
+        let steps = (self.missed + 1) as f32;
+        self.velocity = ((bbox.x - self.bbox.x) / steps, (bbox.y - self.bbox.y) / steps);
+        self.bbox = bbox.clone();
+        self.missed = 0;
+    }
+
+    fn event(&self, event: TrackEventKind) -> TrackEvent {
+        TrackEvent {
+            event,
+            track_id: self.id,
+            class: self.class.clone(),
+            bbox: self.bbox.clone(),
+        }
+    }
+}
+
+// Suivi des objets d'une caméra (ou session) : association gloutonne par IoU, classe par classe
+#[derive(Debug)]
+pub struct Tracker {
+    config: TrackerConfig,
+    tracks: Vec<Track>,
+    next_id: u64,
+    last_update: Instant,
+}
+
+impl Tracker {
+    pub fn new(config: TrackerConfig) -> Self {
+        Self {
+            config,
+            tracks: Vec::new(),
+            next_id: 1,
+            last_update: Instant::now(),
+        }
+    }
+
+    // Renseigner le track_id de chaque détection et renvoyer les entrées / sorties
+    pub fn update(&mut self, detections: &mut [Detection]) -> Vec<TrackEvent> {
+        self.last_update = Instant::now();
+
+        // Paires (piste, détection) candidates, meilleure IoU d'abord
+        let mut pairs = Vec::new();
+        for (track_index, track) in self.tracks.iter().enumerate() {
+            let predicted = track.predicted();
+            for (detection_index, detection) in detections.iter().enumerate() {
+                if detection.class != track.class {
+                    continue;
+                }
+                let overlap = iou(&predicted, &detection.bbox).max(iou(&track.bbox, &detection.bbox));
+                if overlap >= self.config.iou_threshold {
+                    pairs.push((overlap, track_index, detection_index));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut matched_tracks = vec![false; self.tracks.len()];
+        for (_, track_index, detection_index) in pairs {
+            if matched_tracks[track_index] || detections[detection_index].track_id.is_some() {
+                continue;
+            }
+            matched_tracks[track_index] = true;
+            let track = &mut self.tracks[track_index];
+            track.update(&detections[detection_index].bbox);
+            detections[detection_index].track_id = Some(track.id);
+        }
+
+        let mut events = Vec::new();
+
+        // Pistes non revues : sortie après max_missed images
+        let max_missed = self.config.max_missed;
+        let mut index = 0;
+        self.tracks.retain_mut(|track| {
+            let matched = matched_tracks.get(index).copied().unwrap_or(false);
+            index += 1;
+            if matched {
+                return true;
+            }
+            track.missed += 1;
+            if track.missed > max_missed {
+                events.push(track.event(TrackEventKind::Exit));
+                return false;
+            }
+            true
+        });
+
+        // Détections sans piste : nouveaux objets
+        for detection in detections.iter_mut().filter(|detection| detection.track_id.is_none()) {
+            let track = Track {
+                id: self.next_id,
+                class: detection.class.clone(),
+                bbox: detection.bbox.clone(),
+                velocity: (0.0, 0.0),
+                missed: 0,
+            };
+            self.next_id += 1;
+            detection.track_id = Some(track.id);
+            events.push(track.event(TrackEventKind::Enter));
+            self.tracks.push(track);
+        }
+
+        events
+    }
+}
+
+// Suivi et comptage d'une caméra
+struct Camera {
+    tracker: Tracker,
+    counting: CountingState,
+}
+
+// Trackers par g_id, partagés entre les requêtes
+#[derive(Clone)]
+pub struct TrackerRegistry {
+    config: TrackerConfig,
+    cameras: Arc<Mutex<HashMap<String, Camera>>>,
+}
+
+impl TrackerRegistry {
+    pub fn new(config: TrackerConfig) -> Self {
+        Self {
+            config,
+            cameras: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // Suivre les détections d'une image puis appliquer les règles de comptage de la caméra
+    pub fn update(
+        &self,
+        key: &str,
+        detections: &mut [Detection],
+        rules: &[CountingRule],
+    ) -> (Vec<TrackEvent>, Vec<CountingEvent>) {
+        let mut cameras = self.cameras.lock().unwrap_or_else(|e| e.into_inner());
+        cameras.retain(|_, camera| camera.tracker.last_update.elapsed() < IDLE_TIMEOUT);
+
+        let camera = cameras.entry(key.to_string()).or_insert_with(|| Camera {
+            tracker: Tracker::new(self.config),
+            counting: CountingState::default(),
+        });
+        let track_events = camera.tracker.update(detections);
+        let counting_events = camera.counting.update(rules, detections, &track_events);
+        (track_events, counting_events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detection(class: &str, x: f32) -> Detection {
+        Detection {
+            class: class.to_string(),
+            color: None,
+            confidence: 0.9,
+            bbox: BoundingBox {
+                x,
+                y: 0.0,
+                width: 10.0,
+                height: 10.0,
+            },
+            track_id: None,
+        }
+    }
+
+    fn track_ids(detections: &[Detection]) -> Vec<u64> {
+        detections.iter().map(|detection| detection.track_id.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_keeps_ids_of_moving_objects() {
+        let mut tracker = Tracker::new(TrackerConfig::default());
+
+        let mut frame = vec![detection("STM32", 0.0), detection("STM32", 100.0)];
+        let events = tracker.update(&mut frame);
+        assert_eq!(track_ids(&frame), vec![1, 2]);
+        assert!(events.iter().all(|event| event.event == TrackEventKind::Enter));
+
+        // 5 px puis 8 px par image : l'IoU brute tombe à 0.11, la prédiction suit
+        for offset in [5.0, 13.0, 21.0, 29.0, 37.0] {
+            let mut frame = vec![detection("STM32", 100.0 + offset), detection("STM32", offset)];
+            assert!(tracker.update(&mut frame).is_empty());
+            assert_eq!(track_ids(&frame), vec![2, 1]);
+        }
+    }
+
+    #[test]
+    fn test_reports_enter_and_exit() {
+        let config = TrackerConfig {
+            max_missed: 1,
+            ..Default::default()
+        };
+        let mut tracker = Tracker::new(config);
+
+        tracker.update(&mut [detection("STM32", 0.0)]);
+
+        // Même position, autre classe : nouvel objet
+        let mut frame = [detection("Carte microchip", 0.0)];
+        let events = tracker.update(&mut frame);
+        assert_eq!(frame[0].track_id, Some(2));
+        assert_eq!(events.len(), 1);
+
+        let events = tracker.update(&mut []);
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].event, events[0].track_id), (TrackEventKind::Exit, 1));
+        assert_eq!(tracker.update(&mut []).len(), 1);
+        assert!(tracker.tracks.is_empty());
+    }
+
+    #[test]
+    fn test_registry_is_keyed_per_camera() {
+        let registry = TrackerRegistry::new(TrackerConfig::default());
+        let mut first = [detection("STM32", 0.0)];
+        let mut second = [detection("STM32", 0.0)];
+        registry.update("CAM_1", &mut first, &[]);
+        registry.update("CAM_2", &mut second, &[]);
+        assert_eq!((first[0].track_id, second[0].track_id), (Some(1), Some(1)));
+    }
+}
@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{CredentialsError, Role};
+use crate::database::Database;
+use crate::passwords;
+
+// Compte créé au premier démarrage si la table users est vide (surchargeable via ADMIN_USERNAME / ADMIN_PASSWORD)
+const DEFAULT_ADMIN_USERNAME: &str = "admin";
+
+const MIN_USERNAME_LEN: usize = 3;
+const MIN_PASSWORD_LEN: usize = 6;
+
+// Compte opérateur ; le hash du mot de passe ne quitte jamais la base
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub id: i64,
+    pub username: String,
+    pub role: String,
+    pub enabled: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+// Corps de POST /api/users
+#[derive(Debug, Deserialize)]
+pub struct NewUser {
+    pub username: String,
+    pub password: String,
+    #[serde(default = "default_role")]
+    pub role: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_role() -> String {
+    "user".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+// Corps de PUT /api/users/:id, seuls les champs fournis sont modifiés
+#[derive(Debug, Default, Deserialize)]
+pub struct UserUpdate {
+    pub role: Option<String>,
+    pub enabled: Option<bool>,
+    pub password: Option<String>,
+}
+
+// Corps de POST /api/password (utilisateur connecté)
+#[derive(Debug, Deserialize)]
+pub struct PasswordChange {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+fn validate_role(role: &str) -> Result<(), String> {
+    if Role::parse(role).is_some() {
+        Ok(())
+    } else {
+        let roles: Vec<&str> = Role::ALL.iter().map(|role| role.as_str()).collect();
+        Err(format!("Invalid role: {} (expected one of {})", role, roles.join(", ")))
+    }
+}
+
+fn validate_password(password: &str) -> Result<(), String> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(format!("Password must be at least {} characters long", MIN_PASSWORD_LEN));
+    }
+    Ok(())
+}
+
+impl NewUser {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.username.trim().chars().count() < MIN_USERNAME_LEN {
+            return Err(format!("Username must be at least {} characters long", MIN_USERNAME_LEN));
+        }
+        validate_password(&self.password)?;
+        validate_role(&self.role)
+    }
+}
+
+impl UserUpdate {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(password) = &self.password {
+            validate_password(password)?;
+        }
+        self.role.as_deref().map_or(Ok(()), validate_role)
+    }
+
+    // La modification retire-t-elle à ce compte ses droits d'administrateur ?
+    pub fn revokes_admin(&self, user: &User) -> bool {
+        let admin = Role::Admin.as_str();
+        let is_admin = user.enabled && user.role == admin;
+        let stays_admin = self.enabled.unwrap_or(user.enabled)
+            && self.role.as_deref().unwrap_or(&user.role) == admin;
+        is_admin && !stays_admin
+    }
+}
+
+impl PasswordChange {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_password(&self.new_password)
+    }
+}
+
+// Mot de passe initial tiré au hasard quand ADMIN_PASSWORD n'est pas fourni
+fn generate_admin_password() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+// Premier démarrage : créer un administrateur pour pouvoir se connecter
+pub async fn bootstrap_admin(db: &Database) -> Result<(), CredentialsError> {
+    // Comptes déjà présents : rien à créer, et pas de hachage inutile à chaque démarrage
+    if db.has_users().await? {
+        return Ok(());
+    }
+
+    let username = std::env::var("ADMIN_USERNAME").unwrap_or_else(|_| DEFAULT_ADMIN_USERNAME.to_string());
+    let password = std::env::var("ADMIN_PASSWORD").ok();
+    let generated = password.is_none();
+    let password = password.unwrap_or_else(generate_admin_password);
+
+    if db.create_first_admin(&username, &passwords::hash_password(&password).await?).await? {
+        println!("👤 Compte administrateur créé: {}", username);
+        if generated {
+            // Affiché une seule fois : il n'est stocké que haché
+            println!("🔑 Mot de passe généré (ADMIN_PASSWORD absent): {}", password);
+            println!("⚠️ Notez-le maintenant et changez-le via POST /api/password");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(role: &str, enabled: bool) -> User {
+        User {
+            id: 1,
+            username: "admin".to_string(),
+            role: role.to_string(),
+            enabled,
+            created_at: String::new(),
+            updated_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_validates_users() {
+        let new_user = |username: &str, password: &str, role: &str| NewUser {
+            username: username.to_string(),
+            password: password.to_string(),
+            role: role.to_string(),
+            enabled: true,
+        };
+        assert!(new_user("alice", "secret1", "viewer").validate().is_ok());
+        assert!(new_user("al", "secret1", "viewer").validate().is_err());
+        assert!(new_user("alice", "short", "viewer").validate().is_err());
+        assert!(new_user("alice", "secret1", "root").validate().is_err());
+    }
+
+    #[test]
+    fn test_detects_admin_revocation() {
+        let demote = UserUpdate {
+            role: Some("user".to_string()),
+            ..Default::default()
+        };
+        let disable = UserUpdate {
+            enabled: Some(false),
+            ..Default::default()
+        };
+        assert!(demote.revokes_admin(&user("admin", true)));
+        assert!(disable.revokes_admin(&user("admin", true)));
+        assert!(!disable.revokes_admin(&user("user", true)));
+        assert!(!UserUpdate::default().revokes_admin(&user("admin", true)));
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_xlsxwriter::{Format, FormatAlign, Workbook, Worksheet, XlsxError};
+use tokio::sync::mpsc;
+
+use crate::database::{DailyCadence, DetectionRecord};
+
+pub const CONTENT_TYPE: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+// Nombre maximal de lignes d'une feuille Excel (en-tête compris)
+const MAX_ROWS: u32 = 1_048_576;
+
+const DETECTION_COLUMNS: &[(&str, f64)] = &[
+    ("ID", 8.0),
+    ("G_ID", 28.0),
+    ("Request ID", 38.0),
+    ("Type", 22.0),
+    ("Couleur", 10.0),
+    ("Confiance", 11.0),
+    ("Date/Heure", 20.0),
+    ("Compteur", 10.0),
+    ("Objets", 8.0),
+];
+
+const CADENCE_COLUMNS: &[(&str, f64)] = &[("Jour", 12.0), ("Type", 22.0), ("Cadence", 10.0)];
+
+fn write_header(worksheet: &mut Worksheet, columns: &[(&str, f64)]) -> Result<(), XlsxError> {
+    let header = Format::new()
+        .set_bold()
+        .set_background_color("#DDEBF7")
+        .set_align(FormatAlign::Center);
+
+    for (col, (title, width)) in columns.iter().enumerate() {
+        worksheet.set_column_width(col as u16, *width)?;
+        worksheet.write_string_with_format(0, col as u16, *title, &header)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+    worksheet.autofilter(0, 0, 0, columns.len() as u16 - 1)?;
+    Ok(())
+}
+
+// Formats des cellules typées
+struct CellFormats {
+    datetime: Format,
+    date: Format,
+    percent: Format,
+}
+
+fn write_detection(
+    worksheet: &mut Worksheet,
+    row: u32,
+    record: &DetectionRecord,
+    formats: &CellFormats,
+) -> Result<(), XlsxError> {
+    worksheet.write_number(row, 0, record.id as f64)?;
+    worksheet.write_string(row, 1, &record.g_id)?;
+    worksheet.write_string(row, 2, &record.request_id)?;
+    worksheet.write_string(row, 3, &record.object_type)?;
+    worksheet.write_string(row, 4, &record.color)?;
+    worksheet.write_number_with_format(row, 5, record.confidence, &formats.percent)?;
+    match NaiveDateTime::parse_from_str(&record.datetime, "%Y-%m-%d %H:%M:%S") {
+        Ok(datetime) => worksheet.write_datetime_with_format(row, 6, datetime, &formats.datetime)?,
+        Err(_) => worksheet.write_string(row, 6, &record.datetime)?,
+    };
+    worksheet.write_number(row, 7, record.ref_count as f64)?;
+    worksheet.write_number(row, 8, record.objects.len() as f64)?;
+    Ok(())
+}
+
+fn write_cadence(
+    worksheet: &mut Worksheet,
+    row: u32,
+    entry: &DailyCadence,
+    formats: &CellFormats,
+) -> Result<(), XlsxError> {
+    match NaiveDate::parse_from_str(&entry.day, "%Y-%m-%d") {
+        Ok(day) => worksheet.write_datetime_with_format(row, 0, day, &formats.date)?,
+        Err(_) => worksheet.write_string(row, 0, &entry.day)?,
+    };
+    worksheet.write_string(row, 1, &entry.object_type)?;
+    worksheet.write_number(row, 2, entry.count as f64)?;
+    Ok(())
+}
+
+// Classeur avec une feuille de détections brutes et une feuille de cadence journalière par type.
+// Appelée dans spawn_blocking : les détections sont lues au fil de l'eau depuis la base.
+pub fn build_workbook(
+    mut records: mpsc::Receiver<Result<DetectionRecord, sqlx::Error>>,
+    cadence: &[DailyCadence],
+) -> Result<Vec<u8>, String> {
+    let formats = CellFormats {
+        datetime: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+        date: Format::new().set_num_format("yyyy-mm-dd"),
+        percent: Format::new().set_num_format("0.0%"),
+    };
+
+    let mut workbook = Workbook::new();
+
+    // Mode mémoire constante : les lignes sont écrites sur disque au fur et à mesure
+    let detections = workbook.add_worksheet_with_constant_memory();
+    detections.set_name("Détections").map_err(|e| e.to_string())?;
+    write_header(detections, DETECTION_COLUMNS).map_err(|e| e.to_string())?;
+
+    let mut row = 1;
+    while let Some(record) = records.blocking_recv() {
+        let record = record.map_err(|e| e.to_string())?;
+        if row >= MAX_ROWS {
+            eprintln!("⚠️ Export XLSX tronqué à {} détections", MAX_ROWS - 1);
+            break;
+        }
+        write_detection(detections, row, &record, &formats).map_err(|e| e.to_string())?;
+        row += 1;
+    }
+
+    let cadence_sheet = workbook.add_worksheet();
+    cadence_sheet.set_name("Cadence").map_err(|e| e.to_string())?;
+    write_header(cadence_sheet, CADENCE_COLUMNS).map_err(|e| e.to_string())?;
+    for (index, entry) in cadence.iter().enumerate() {
+        write_cadence(cadence_sheet, index as u32 + 1, entry, &formats).map_err(|e| e.to_string())?;
+    }
+
+    workbook.save_to_buffer().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_workbook() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(Ok(DetectionRecord {
+            id: 1,
+            request_id: "r1".to_string(),
+            g_id: "CAM_1".to_string(),
+            object_type: "STM32".to_string(),
+            color: "blue".to_string(),
+            confidence: 0.92,
+            datetime: "2024-01-15 10:30:00".to_string(),
+            ref_count: 1,
+            device_id: None,
+            objects: Vec::new(),
+        }))
+        .unwrap();
+        drop(tx);
+
+        let cadence = vec![DailyCadence {
+            day: "2024-01-15".to_string(),
+            object_type: "STM32".to_string(),
+            count: 1,
+        }];
+
+        let bytes = build_workbook(rx, &cadence).unwrap();
+        assert!(bytes.starts_with(b"PK"));
+    }
+}
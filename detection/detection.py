@@ -18,6 +18,10 @@ import os
 # Configuration de l'API
 API_BASE_URL = "http://localhost:3000/api"
 API_ENDPOINT = f"{API_BASE_URL}/detections"
+# Clé d'API de l'appareil (créée via POST /api/devices)
+DEVICE_API_KEY = os.environ.get("DEVICE_API_KEY")
+HEARTBEAT_ENDPOINT = f"{API_BASE_URL}/devices/heartbeat"
+HEARTBEAT_INTERVAL = 30.0  # Secondes entre deux battements (serveur : DEVICE_OFFLINE_SECS)
 
 # Configuration des couleurs HSV (Hue, Saturation, Value)
 COLORS = {
@@ -46,14 +50,16 @@ COLORS = {
 }
 
 class DetectionSystem:
-    def __init__(self, camera_index=0, api_url=API_ENDPOINT):
+    def __init__(self, camera_index=0, api_url=API_ENDPOINT, api_key=DEVICE_API_KEY):
         self.camera_index = camera_index
         self.api_url = api_url
+        self.api_key = api_key
         self.cap = None
         self.running = False
         self.detection_enabled = True
         self.detection_interval = 1.0  # Secondes entre détections
         self.last_detection_time = 0
+        self.last_heartbeat_time = 0
         
         # Compteurs
         self.counters = {color: 0 for color in COLORS.keys()}
@@ -100,10 +106,14 @@ class DetectionSystem:
                 "color": color
             }
             
+            headers = {'Content-Type': 'application/json'}
+            if self.api_key:
+                headers['X-API-Key'] = self.api_key
+            
             response = requests.post(
                 self.api_url,
                 json=payload,
-                headers={'Content-Type': 'application/json'},
+                headers=headers,
                 timeout=5
             )
             
@@ -126,6 +136,23 @@ class DetectionSystem:
         
         return False
 
+    def send_heartbeat(self):
+        """Signaler au backend que l'appareil est en ligne"""
+        if not self.api_key:
+            return False
+        try:
+            response = requests.post(
+                HEARTBEAT_ENDPOINT,
+                headers={'X-API-Key': self.api_key},
+                timeout=5
+            )
+            if response.status_code != 200:
+                print(f"❌ Heartbeat HTTP {response.status_code}: {response.text}")
+            return response.status_code == 200
+        except requests.exceptions.RequestException as e:
+            print(f"🌐 Heartbeat impossible: {e}")
+        return False
+
     def detect_color_objects(self, frame):
         """Détecter les objets colorés dans l'image"""
         hsv = cv2.cvtColor(frame, cv2.COLOR_BGR2HSV)
@@ -300,6 +327,11 @@ class DetectionSystem:
                     
                     self.last_detection_time = current_time
                 
+                # Battement de cœur, même sans détection
+                if current_time - self.last_heartbeat_time > HEARTBEAT_INTERVAL:
+                    self.send_heartbeat()
+                    self.last_heartbeat_time = current_time
+                
                 # Interface utilisateur
                 self.draw_ui(frame)
                 
@@ -402,6 +434,8 @@ def main():
                        help='Index de la caméra (défaut: 0)')
     parser.add_argument('--api-url', '-a', type=str, default=API_ENDPOINT,
                        help=f'URL de l\'API (défaut: {API_ENDPOINT})')
+    parser.add_argument('--api-key', '-k', type=str, default=DEVICE_API_KEY,
+                       help='Clé d\'API de l\'appareil (défaut: variable DEVICE_API_KEY)')
     parser.add_argument('--sensitivity', '-s', choices=['low', 'medium', 'high'], 
                        default='medium', help='Niveau de sensibilité (défaut: medium)')
     parser.add_argument('--no-api', action='store_true', 
@@ -421,7 +455,7 @@ def main():
     
     # Test de l'API si demandé
     if args.test_api:
-        system = DetectionSystem(args.camera, args.api_url)
+        system = DetectionSystem(args.camera, args.api_url, args.api_key)
         if system.test_api_connection():
             print("✅ Test API réussi")
             return 0
@@ -430,7 +464,7 @@ def main():
             return 1
     
     # Créer le système de détection
-    system = DetectionSystem(args.camera, args.api_url)
+    system = DetectionSystem(args.camera, args.api_url, args.api_key)
     
     # Configurer la sensibilité
     system.adjust_detection_sensitivity(args.sensitivity)
@@ -440,6 +474,8 @@ def main():
         system.detection_enabled = False
         print("⚠️ Mode hors-ligne activé (pas d'envoi API)")
     else:
+        if not args.api_key:
+            print("⚠️ Aucune clé d'appareil (--api-key ou DEVICE_API_KEY) : l'API refusera les détections (401)")
         # Tester la connexion API
         system.test_api_connection()
     
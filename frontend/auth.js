@@ -0,0 +1,53 @@
+// Jetons de session partagés par les pages du frontend (sessionStorage, rempli par login.html)
+
+// Pas de jeton : passer par la page de connexion, puis revenir sur la page courante
+function requireLogin(page) {
+    if (!sessionStorage.getItem('admin_token')) {
+        window.location.href = `login.html?next=${encodeURIComponent(page)}`;
+        return false;
+    }
+    return true;
+}
+
+// Jeton d'accès expiré : un seul échange du jeton de rafraîchissement, puis nouvel essai
+async function authFetch(url, options = {}) {
+    const send = () => fetch(url, {
+        ...options,
+        headers: {
+            ...options.headers,
+            'Authorization': `Bearer ${sessionStorage.getItem('admin_token')}`
+        }
+    });
+
+    let response = await send();
+    if (response.status === 401) {
+        if (!(await refreshSession())) {
+            sessionStorage.clear();
+            window.location.href = 'login.html';
+            return response;
+        }
+        response = await send();
+    }
+    return response;
+}
+
+async function refreshSession() {
+    const refreshToken = sessionStorage.getItem('refresh_token');
+    if (!refreshToken) {
+        return false;
+    }
+
+    const response = await fetch('http://localhost:3000/api/refresh', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ refresh_token: refreshToken })
+    });
+    if (!response.ok) {
+        return false;
+    }
+
+    const result = await response.json();
+    sessionStorage.setItem('admin_token', result.data.token);
+    sessionStorage.setItem('refresh_token', result.data.refresh_token);
+    return true;
+}
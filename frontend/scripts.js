@@ -13,6 +13,7 @@ let detectionCounts = {
     blue: 0
 };
 let lastDetectionTime = 0;
+let isFrameInFlight = false;
 let fpsCounter = 0;
 let fpsInterval = null;
 
@@ -248,9 +249,11 @@ function stopDetection() {
 // ===== TRAITEMENT DES FRAMES =====
 
 /**
- * Traite une frame pour la détection de couleurs
+ * Envoie une frame au backend pour la détection de couleurs
  */
-function processFrame() {
+async function processFrame() {
+    if (isFrameInFlight) return;
+    
     try {
         const video = document.getElementById('cameraFeed');
         const canvas = document.getElementById('detectionOverlay');
@@ -267,10 +270,9 @@ function processFrame() {
         
         // Capturer la frame actuelle
         tempCtx.drawImage(video, 0, 0);
-        const imageData = tempCtx.getImageData(0, 0, tempCanvas.width, tempCanvas.height);
         
-        // Analyser les couleurs
-        const detections = analyzeColors(imageData, tempCanvas.width, tempCanvas.height);
+        isFrameInFlight = true;
+        const detections = await detectOnServer(tempCanvas.toDataURL('image/jpeg', 0.8));
         
         // Dessiner les détections sur l'overlay
         drawDetections(canvas, detections);
@@ -283,147 +285,44 @@ function processFrame() {
         
     } catch (error) {
         console.error('Erreur traitement frame:', error);
+    } finally {
+        isFrameInFlight = false;
     }
 }
 
 /**
- * Analyse les couleurs dans l'image
+ * Détecte les objets via POST /detect (seuils HSV et NMS côté serveur)
  */
-function analyzeColors(imageData, width, height) {
-    const data = imageData.data;
-    const detections = [];
+async function detectOnServer(imageDataUrl) {
     const sensitivity = parseInt(document.getElementById('detectionSensitivity').value);
-    const threshold = 255 - (sensitivity * 20); // Plus la sensibilité est haute, plus le seuil est bas
-    
-    // Définition des seuils de couleur adaptés à la sensibilité
-    const colorThresholds = {
-        red: {
-            min: { r: Math.max(100, threshold), g: 0, b: 0 },
-            max: { r: 255, g: 80, b: 80 },
-            label: 'Carte microchip',
-            color: 'red'
-        },
-        green: {
-            min: { r: 0, g: Math.max(100, threshold), b: 0 },
-            max: { r: 80, g: 255, b: 80 },
-            label: 'Carte personnalisée',
-            color: 'green'
-        },
-        blue: {
-            min: { r: 0, g: 0, b: Math.max(100, threshold) },
-            max: { r: 80, g: 80, b: 255 },
-            label: 'STM32',
-            color: 'blue'
-        }
-    };
+    // Plus la sensibilité est haute, plus le seuil de confiance est bas
+    const confidence = Math.min(0.95, Math.max(0.05, 1 - sensitivity / 10));
+    
+    const response = await fetch('/detect', {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({
+            image_data: imageDataUrl,
+            model_type: 'color',
+            confidence: confidence
+        })
+    });
     
-    // Analyser par zones pour améliorer les performances
-    const blockSize = 20;
-    for (let y = 0; y < height; y += blockSize) {
-        for (let x = 0; x < width; x += blockSize) {
-            const index = (y * width + x) * 4;
-            
-            if (index < data.length) {
-                const r = data[index];
-                const g = data[index + 1];
-                const b = data[index + 2];
-                
-                // Vérifier chaque couleur
-                for (const [colorName, threshold] of Object.entries(colorThresholds)) {
-                    if (isColorMatch(r, g, b, threshold)) {
-                        detections.push({
-                            x: x + blockSize / 2,
-                            y: y + blockSize / 2,
-                            width: blockSize * 2,
-                            height: blockSize * 2,
-                            color: colorName,
-                            label: threshold.label,
-                            confidence: calculateConfidence(r, g, b, threshold)
-                        });
-                    }
-                }
-            }
-        }
+    const result = await response.json();
+    if (!response.ok || !result.success) {
+        throw new Error(result.message || `HTTP ${response.status}`);
     }
     
-    // Grouper les détections proches
-    return groupNearbyDetections(detections);
-}
-
-/**
- * Vérifie si une couleur RGB correspond à un seuil
- */
-function isColorMatch(r, g, b, threshold) {
-    return r >= threshold.min.r && r <= threshold.max.r &&
-           g >= threshold.min.g && g <= threshold.max.g &&
-           b >= threshold.min.b && b <= threshold.max.b;
-}
-
-/**
- * Calcule la confiance de détection
- */
-function calculateConfidence(r, g, b, threshold) {
-    const centerR = (threshold.min.r + threshold.max.r) / 2;
-    const centerG = (threshold.min.g + threshold.max.g) / 2;
-    const centerB = (threshold.min.b + threshold.max.b) / 2;
-    
-    const distance = Math.sqrt(
-        Math.pow(r - centerR, 2) +
-        Math.pow(g - centerG, 2) +
-        Math.pow(b - centerB, 2)
-    );
-    
-    return Math.max(0, 1 - distance / 255);
-}
-
-/**
- * Groupe les détections proches pour éviter les doublons
- */
-function groupNearbyDetections(detections) {
-    const grouped = [];
-    const processed = new Set();
-    
-    detections.forEach((detection, index) => {
-        if (processed.has(index)) return;
-        
-        const group = [detection];
-        processed.add(index);
-        
-        // Chercher les détections proches de la même couleur
-        detections.forEach((other, otherIndex) => {
-            if (processed.has(otherIndex) || otherIndex === index) return;
-            
-            const distance = Math.sqrt(
-                Math.pow(detection.x - other.x, 2) +
-                Math.pow(detection.y - other.y, 2)
-            );
-            
-            if (distance < 100 && detection.color === other.color) {
-                group.push(other);
-                processed.add(otherIndex);
-            }
-        });
-        
-        // Créer une détection groupée
-        if (group.length > 0) {
-            const avgX = group.reduce((sum, d) => sum + d.x, 0) / group.length;
-            const avgY = group.reduce((sum, d) => sum + d.y, 0) / group.length;
-            const maxConfidence = Math.max(...group.map(d => d.confidence));
-            
-            grouped.push({
-                x: avgX,
-                y: avgY,
-                width: Math.max(...group.map(d => d.width)),
-                height: Math.max(...group.map(d => d.height)),
-                color: detection.color,
-                label: detection.label,
-                confidence: maxConfidence,
-                count: group.length
-            });
-        }
-    });
-    
-    return grouped;
+    // Le serveur renvoie le coin haut-gauche, l'overlay travaille en centre
+    return (result.detections || []).map(detection => ({
+        x: detection.bbox.x + detection.bbox.width / 2,
+        y: detection.bbox.y + detection.bbox.height / 2,
+        width: detection.bbox.width,
+        height: detection.bbox.height,
+        color: detection.color,
+        label: detection.class,
+        confidence: detection.confidence
+    }));
 }
 
 /**
@@ -572,7 +471,7 @@ async function loadRecentDetections() {
             const result = await response.json();
             
             if (result.success) {
-                updateRecentList(result.data.recent || []);
+                updateRecentList(result.data.recent_detections || []);
                 updateStatsFromAPI(result.data);
             }
         }